@@ -0,0 +1,50 @@
+//! Measures the overhead of a `Measurements::measure` call while 1 and 64
+//! threads record into the same measurements.
+//!
+//! ```text
+//! cargo run --release --example measure_overhead
+//! ```
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ycsb::core::measurement::Measurements;
+
+const CALLS: u32 = 1_000_000;
+
+/// Returns the average CPU time of a `measure` call, estimated from the
+/// elapsed time of all the threads running on at most `cores` cores.
+fn overhead(threads: usize, cores: usize) -> Duration {
+    let measurements = Measurements::new();
+    let start = Instant::now();
+    let handles: Vec<_> = (0..threads)
+        .map(|_| {
+            let measurements = measurements.clone();
+            thread::spawn(move || {
+                for i in 0..CALLS {
+                    let latency = Duration::from_nanos(u64::from(i % 100_000));
+                    measurements.measure("READ", latency, true);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    let elapsed = start.elapsed();
+    let infos = measurements.info();
+    assert_eq!(infos[0].1.count, u64::from(CALLS) * threads as u64);
+    elapsed * threads.min(cores) as u32 / CALLS / threads as u32
+}
+
+fn main() {
+    let cores = core_affinity::get_core_ids().map_or(1, |ids| ids.len());
+    for &threads in &[1, 64] {
+        println!(
+            "{} threads on {} cores: {:?} per measure call",
+            threads,
+            cores,
+            overhead(threads, cores)
+        );
+    }
+}
@@ -0,0 +1,356 @@
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::time::Duration;
+
+use anyhow::Result;
+use structopt::StructOpt;
+
+use crate::{
+    core::{
+        benchmark::{Benchmark, BenchmarkResult, Phase},
+        measurement::MeasurementInfo,
+        properties::{Properties, WORKLOAD_PROPERTY},
+        runner::Shutdown,
+        workload::{create_workload, workload_names},
+    },
+    db::{create_async_db, create_db, db_names},
+};
+
+#[derive(StructOpt)]
+struct Ycsb {
+    #[structopt(short, long, default_value = "1", parse(try_from_str = parse_threads))]
+    threads: usize,
+    /// The database binding, or `list` to show the registered bindings.
+    #[structopt(short, long)]
+    db: Option<String>,
+    #[structopt(short, long)]
+    config: Option<String>,
+    /// The workload, overriding the `workload` property, or `list` to show
+    /// the registered workloads.
+    #[structopt(short, long)]
+    workload: Option<String>,
+    /// Seconds between two status reports, or 0 for no status reports.
+    #[structopt(short, long, default_value = "10")]
+    status_interval: u64,
+    /// Pins every client thread to a CPU core.
+    #[structopt(long)]
+    pin_cpus: bool,
+    #[structopt(subcommand)]
+    cmd: Option<Command>,
+}
+#[derive(StructOpt, Clone)]
+enum Command {
+    Load,
+    Run,
+    LoadRun,
+    /// Loads once, then runs the workload with every thread count.
+    Sweep {
+        /// The thread counts to run with, e.g. `1,2,4,8`.
+        #[structopt(
+            long,
+            use_delimiter = true,
+            required = true,
+            parse(try_from_str = parse_threads)
+        )]
+        thread_counts: Vec<usize>,
+        /// Writes the result table to a CSV file.
+        #[structopt(long)]
+        csv: Option<String>,
+        /// Writes the result table to a JSON file.
+        #[structopt(long)]
+        json: Option<String>,
+    },
+}
+
+/// Parses a thread count, which must be positive.
+fn parse_threads(threads: &str) -> Result<usize> {
+    match threads.parse()? {
+        0 => Err(anyhow!("the thread count must be positive")),
+        threads => Ok(threads),
+    }
+}
+
+/// The result of an operation in a run of the sweep.
+struct SweepRow {
+    threads: usize,
+    total_ops: f64,
+    operation: String,
+    info: MeasurementInfo,
+}
+
+fn sweep_rows(result: BenchmarkResult) -> Vec<SweepRow> {
+    let total_ops = result.count as f64 / result.elapsed.as_secs_f64();
+    let threads = result.threads;
+    result
+        .operations
+        .into_iter()
+        .map(|(operation, info)| SweepRow {
+            threads,
+            total_ops,
+            operation,
+            info,
+        })
+        .collect()
+}
+
+fn print_sweep(rows: &[SweepRow]) {
+    println!(
+        "{:>8} {:>14} {:<20} {:>14} {:>12} {:>12}",
+        "threads", "total ops/sec", "operation", "ops/sec", "p50(us)", "p99(us)"
+    );
+    for row in rows {
+        println!(
+            "{:>8} {:>14.2} {:<20} {:>14.2} {:>12} {:>12}",
+            row.threads,
+            row.total_ops,
+            row.operation,
+            row.info.ops,
+            row.info.per50th.as_micros(),
+            row.info.per99th.as_micros()
+        );
+    }
+}
+
+fn write_sweep_csv(path: &str, rows: &[SweepRow]) -> Result<()> {
+    let mut file = File::create(path)?;
+    writeln!(file, "threads,total_ops,operation,ops,p50_us,p99_us")?;
+    for row in rows {
+        writeln!(
+            file,
+            "{},{:.2},{},{:.2},{},{}",
+            row.threads,
+            row.total_ops,
+            row.operation,
+            row.info.ops,
+            row.info.per50th.as_micros(),
+            row.info.per99th.as_micros()
+        )?;
+    }
+    Ok(())
+}
+
+fn write_sweep_json(path: &str, rows: &[SweepRow]) -> Result<()> {
+    let rows = rows
+        .iter()
+        .map(|row| {
+            format!(
+                r#"  {{"threads": {}, "total_ops": {:.2}, "operation": {}, "ops": {:.2}, "p50_us": {}, "p99_us": {}}}"#,
+                row.threads,
+                row.total_ops,
+                json_string(&row.operation),
+                row.info.ops,
+                row.info.per50th.as_micros(),
+                row.info.per99th.as_micros()
+            )
+        })
+        .collect::<Vec<_>>();
+    let mut file = File::create(path)?;
+    writeln!(file, "[\n{}\n]", rows.join(",\n"))?;
+    Ok(())
+}
+
+/// Quotes `s` as a JSON string.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn print_result(result: &BenchmarkResult) {
+    for step in &result.steps {
+        let end = match step.end {
+            Some(end) => format!("{}s", end.as_secs()),
+            None => "end".to_string(),
+        };
+        let target = if step.start_target == step.end_target {
+            step.start_target.to_string()
+        } else {
+            format!("{}->{}", step.start_target, step.end_target)
+        };
+        println!(
+            "[STEP {}s-{}, target {} ops/sec] {:.2} ops/sec",
+            step.start.as_secs(),
+            end,
+            target,
+            step.status.ops
+        );
+        for (name, info) in &step.status.operations {
+            println!("  [{}] {}", name, info);
+        }
+    }
+    for (name, info) in &result.operations {
+        println!("[{}] {}", name, info);
+    }
+    for (name, count) in &result.counters {
+        println!("[{}] count: {}", name, count);
+    }
+    if let Some(anomaly) = &result.anomaly {
+        println!("[VALIDATE] anomaly: {}", anomaly);
+    }
+}
+
+/// Runs the `ycsb` command line with the bindings registered by
+/// `db::register_db`, so that a crate with its own bindings can reuse it:
+///
+/// ```ignore
+/// fn main() -> anyhow::Result<()> {
+///     ycsb::db::register_db("mydb", |props| Ok(Arc::new(MyDb::new(props)?)));
+///     ycsb::cli::main()
+/// }
+/// ```
+pub fn main() -> Result<()> {
+    let mut runtime = tokio::runtime::Builder::new()
+        .threaded_scheduler()
+        .enable_all()
+        .build()?;
+    runtime.block_on(run(Ycsb::from_args()))
+}
+
+async fn run(opt: Ycsb) -> Result<()> {
+    if opt.db.as_deref() == Some("list") {
+        for name in db_names() {
+            println!("{}", name);
+        }
+        return Ok(());
+    }
+    if opt.workload.as_deref() == Some("list") {
+        for name in workload_names() {
+            println!("{}", name);
+        }
+        return Ok(());
+    }
+    let db_name = opt
+        .db
+        .as_ref()
+        .ok_or_else(|| anyhow!("the database binding is required"))?;
+    let config = opt
+        .config
+        .as_ref()
+        .ok_or_else(|| anyhow!("the config file is required"))?;
+    let cmd = opt
+        .cmd
+        .clone()
+        .ok_or_else(|| anyhow!("the subcommand is required"))?;
+
+    let config = File::open(config).expect("cannot open config file");
+    let config_reader = BufReader::new(config);
+    let mut props = Properties::load(config_reader).expect("load properties failed");
+    if let Some(workload) = &opt.workload {
+        props.set_property(WORKLOAD_PROPERTY, workload.clone());
+    }
+
+    let shutdown = Shutdown::default();
+    let ctrl_c_shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            println!("====== Interrupted, shutting down clients. ======");
+            ctrl_c_shutdown.signal();
+        }
+    });
+
+    // The phases share the workload, continuing its key sequences.
+    let workload = create_workload(&props)?;
+    let benchmark = if props.get_concurrency() > 1 || props.is_open_loop()? {
+        let db = create_async_db(db_name, &props).expect("create db failed");
+        Benchmark::new_async(props, db)
+    } else {
+        let db = create_db(db_name, &props).expect("create db failed");
+        Benchmark::new(props, db)
+    };
+    let benchmark = benchmark
+        .workload(workload)
+        .status_interval(Duration::from_secs(opt.status_interval))
+        .pin_cpus(opt.pin_cpus)
+        .shutdown_signal(shutdown.clone());
+
+    let (load_phase, run_threads) = match &cmd {
+        Command::Load => (true, vec![]),
+        Command::Run => (false, vec![opt.threads]),
+        Command::LoadRun => (true, vec![opt.threads]),
+        Command::Sweep { thread_counts, .. } => (true, thread_counts.clone()),
+    };
+    let sweep = matches!(cmd, Command::Sweep { .. });
+    if load_phase {
+        let result = benchmark
+            .clone()
+            .phase(Phase::Load)
+            .threads(opt.threads)
+            .execute()
+            .await?;
+        print_result(&result);
+    }
+    let mut rows = Vec::new();
+    for (i, threads) in run_threads.into_iter().enumerate() {
+        if shutdown.is_shutdown() {
+            break;
+        }
+        if load_phase && i == 0 {
+            println!("====== Load data done. ======");
+        }
+        if sweep {
+            println!("====== Run with {} threads. ======", threads);
+        }
+        let result = benchmark
+            .clone()
+            .phase(Phase::Run)
+            .threads(threads)
+            .execute()
+            .await?;
+        print_result(&result);
+        rows.extend(sweep_rows(result));
+    }
+
+    if let Command::Sweep { csv, json, .. } = &cmd {
+        println!("====== Sweep done. ======");
+        print_sweep(&rows);
+        if let Some(path) = csv {
+            write_sweep_csv(path, &rows)?;
+        }
+        if let Some(path) = json {
+            write_sweep_json(path, &rows)?;
+        }
+    }
+    println!("Test exited");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_string() {
+        for &(s, quoted) in &[
+            ("", r#""""#),
+            ("READ-MODIFY-WRITE", r#""READ-MODIFY-WRITE""#),
+            (r#"say "hi""#, r#""say \"hi\"""#),
+            (r"C:\tmp\", r#""C:\\tmp\\""#),
+            (
+                "a\nb\tc\r\u{0}\u{1f}\u{7f}",
+                r#""a\u000ab\u0009c\u000d\u0000\u001f\u007f""#,
+            ),
+            ("héllo ✓", r#""héllo ✓""#),
+        ] {
+            assert_eq!(json_string(s), quoted);
+        }
+    }
+
+    #[test]
+    fn test_parse_threads() {
+        assert_eq!(parse_threads("1").unwrap(), 1);
+        assert_eq!(parse_threads("64").unwrap(), 64);
+        for threads in &["0", "-1", "", "four"] {
+            assert!(parse_threads(threads).is_err(), "{}", threads);
+        }
+    }
+}
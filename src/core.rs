@@ -1,6 +1,9 @@
+pub mod benchmark;
 pub mod client;
 pub mod db;
 pub mod generator;
 pub mod measurement;
 pub mod properties;
+pub mod runner;
+pub mod schedule;
 pub mod workload;
@@ -0,0 +1,526 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use futures::{
+    future::{self, Either},
+    stream::{self, FuturesUnordered},
+    StreamExt,
+};
+use tokio::{runtime, task::JoinHandle, time};
+
+use super::client::{AsyncClient, Client};
+use super::db::{AsyncDb, Db, SyncDbAdapter};
+use super::measurement::{MeasurementInfo, Measurements, Status, StatusReporter};
+use super::properties::Properties;
+use super::runner::{ClientRunner, Shutdown};
+use super::schedule::{TargetSchedule, Throttle};
+use super::workload::{create_workload, split_ops, Workload};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    Load,
+    Run,
+}
+
+/// The throughput and latencies of a step of the target schedule.
+pub struct StepResult {
+    pub start: Duration,
+    /// The end of the step, or `None` for the last step.
+    pub end: Option<Duration>,
+    pub start_target: f64,
+    /// The target at the end of a linear ramp, or `start_target` otherwise.
+    pub end_target: f64,
+    pub status: Status,
+}
+
+pub struct BenchmarkResult {
+    pub phase: Phase,
+    pub threads: usize,
+    pub elapsed: Duration,
+    /// The number of finished operations.
+    pub count: u64,
+    pub operations: Vec<(String, MeasurementInfo)>,
+    pub counters: Vec<(String, u64)>,
+    pub steps: Vec<StepResult>,
+    /// The violated invariant of the workload found after the run phase.
+    pub anomaly: Option<String>,
+}
+
+#[derive(Clone)]
+enum Backend {
+    Sync(Arc<dyn Db>),
+    Async(Arc<dyn AsyncDb>),
+}
+
+/// Runs a phase of the workload against a `Db` and collects its results.
+///
+/// The clients of a synchronous `Db` run on their own OS threads, unless
+/// `concurrency` or an open loop asks for several requests in flight, in
+/// which case they run on the async runtime through `SyncDbAdapter`.
+#[derive(Clone)]
+pub struct Benchmark {
+    props: Properties,
+    db: Backend,
+    threads: usize,
+    phase: Phase,
+    workload: Option<Arc<dyn Workload>>,
+    status_interval: Option<Duration>,
+    pin_cpus: bool,
+    shutdown: Shutdown,
+}
+
+impl Benchmark {
+    pub fn new(props: Properties, db: Arc<dyn Db>) -> Self {
+        Self::with_backend(props, Backend::Sync(db))
+    }
+
+    pub fn new_async(props: Properties, db: Arc<dyn AsyncDb>) -> Self {
+        Self::with_backend(props, Backend::Async(db))
+    }
+
+    fn with_backend(props: Properties, db: Backend) -> Self {
+        Self {
+            props,
+            db,
+            threads: 1,
+            phase: Phase::Run,
+            workload: None,
+            status_interval: None,
+            pin_cpus: false,
+            shutdown: Shutdown::default(),
+        }
+    }
+
+    /// Runs the phase with `threads` clients, which must be positive.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    pub fn phase(mut self, phase: Phase) -> Self {
+        self.phase = phase;
+        self
+    }
+
+    /// Runs the phase with `workload` rather than a new workload created from
+    /// the properties, so that the phases running the same workload share
+    /// its key space: the keys inserted by a phase stay readable by the next
+    /// ones, which insert new keys after them.
+    pub fn workload(mut self, workload: Arc<dyn Workload>) -> Self {
+        self.workload = Some(workload);
+        self
+    }
+
+    /// Prints a status line every `interval` while the phase runs, or none
+    /// if `interval` is zero.
+    pub fn status_interval(mut self, interval: Duration) -> Self {
+        self.status_interval = Some(interval).filter(|interval| *interval > Duration::from_secs(0));
+        self
+    }
+
+    /// Pins every client thread to a CPU core.
+    pub fn pin_cpus(mut self, pin_cpus: bool) -> Self {
+        self.pin_cpus = pin_cpus;
+        self
+    }
+
+    /// Stops the clients early once `shutdown` is signaled.
+    pub fn shutdown_signal(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Runs the phase on a new runtime, blocking until it finishes.
+    pub fn run(&self) -> Result<BenchmarkResult> {
+        let mut rt = runtime::Builder::new()
+            .threaded_scheduler()
+            .enable_all()
+            .build()?;
+        rt.block_on(self.execute())
+    }
+
+    /// Runs the phase on the current runtime.
+    pub async fn execute(&self) -> Result<BenchmarkResult> {
+        if self.threads == 0 {
+            return Err(anyhow!("thread count must be positive"));
+        }
+        let open_loop = self.props.is_open_loop()?;
+        let workload = match &self.workload {
+            Some(workload) => workload.clone(),
+            None => create_workload(&self.props)?,
+        };
+        let (measurements, steps) = match (&self.db, self.phase) {
+            (Backend::Sync(db), Phase::Load) if self.props.get_concurrency() <= 1 => {
+                (self.load(workload.as_ref(), db.clone()).await?, Vec::new())
+            }
+            (Backend::Sync(db), Phase::Run) if self.props.get_concurrency() <= 1 && !open_loop => {
+                self.run_sync(workload.as_ref(), db.clone()).await?
+            }
+            (_, Phase::Load) => (
+                self.load_async(workload.as_ref(), self.async_db()).await?,
+                Vec::new(),
+            ),
+            (_, Phase::Run) if open_loop => {
+                self.run_open_loop(workload.as_ref(), self.async_db())
+                    .await?
+            }
+            (_, Phase::Run) => self.run_async(workload.as_ref(), self.async_db()).await?,
+        };
+        let anomaly = match self.phase {
+            Phase::Load => None,
+            Phase::Run => self.validate(&workload).await?,
+        };
+        workload.cleanup()?;
+        Ok(BenchmarkResult {
+            phase: self.phase,
+            threads: self.threads,
+            elapsed: measurements.elapsed(),
+            count: measurements.operations(),
+            operations: measurements.info(),
+            counters: measurements.counters(),
+            steps,
+            anomaly,
+        })
+    }
+
+    /// Checks the invariant of the workload, if it has one, returning the
+    /// violation.
+    async fn validate(&self, workload: &Arc<dyn Workload>) -> Result<Option<String>> {
+        let result = match &self.db {
+            Backend::Sync(db) => {
+                let (workload, db) = (workload.clone(), db.clone());
+                tokio::task::spawn_blocking(move || workload.validate(db.as_ref())).await?
+            }
+            Backend::Async(db) => workload.validate_async(db.as_ref()).await,
+        };
+        result.map_err(|e| e.context("cannot validate the workload"))
+    }
+
+    fn async_db(&self) -> Arc<dyn AsyncDb> {
+        match &self.db {
+            Backend::Sync(db) => Arc::new(SyncDbAdapter::new(db.clone())),
+            Backend::Async(db) => db.clone(),
+        }
+    }
+
+    fn run_measurements(&self) -> Measurements {
+        Measurements::new().warmup(self.props.get_warmup_time(), self.props.get_warmup_ops())
+    }
+
+    /// Reports the status of the clients every `status_interval` until they
+    /// finish.
+    async fn wait_clients<F, T>(
+        &self,
+        clients: F,
+        measurements: &Measurements,
+        total: u64,
+    ) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        let status_interval = match self.status_interval {
+            Some(status_interval) => status_interval,
+            None => return clients.await,
+        };
+        let done = Shutdown::default();
+        let mut interval = time::interval(status_interval);
+        let mut reporter = StatusReporter::new(measurements.clone(), total);
+        let reporter_measurements = measurements.clone();
+        let reporter_done = done.clone();
+        tokio::spawn(async move {
+            let mut warming_up = reporter_measurements.warming_up();
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if reporter_done.is_shutdown() {
+                    break;
+                }
+                if reporter_measurements.warming_up() {
+                    println!("warming up, measurement not started");
+                    continue;
+                }
+                if warming_up {
+                    println!("====== Warm-up done, measurement started. ======");
+                    warming_up = false;
+                }
+                println!("{}", reporter.report());
+            }
+        });
+
+        let result = clients.await;
+        done.signal();
+        result
+    }
+
+    async fn load(&self, workload: &dyn Workload, db: Arc<dyn Db>) -> Result<Measurements> {
+        let measurements = Measurements::new();
+        let batch_size = self.props.get_batch_size()?;
+        let mut runner = ClientRunner::new("ycsb-load")
+            .pin_cpus(self.pin_cpus)
+            .shutdown_signal(self.shutdown.clone());
+        for i in 0..self.threads {
+            let workload = workload.init_client(i, self.threads, measurements.clone())?;
+            let client = Client::new(db.clone(), workload, measurements.clone());
+            let record_count = split_ops(self.props.get_insert_count(), self.threads, i);
+            runner.spawn(move |shutdown| {
+                for count in batches(record_count, batch_size) {
+                    if shutdown.is_shutdown() {
+                        break;
+                    }
+                    let _ = client.do_insert(count);
+                }
+            })?;
+        }
+        let clients = async move { tokio::task::spawn_blocking(move || runner.join()).await? };
+        self.wait_clients(clients, &measurements, self.props.get_insert_count())
+            .await?;
+        Ok(measurements)
+    }
+
+    async fn run_sync(
+        &self,
+        workload: &dyn Workload,
+        db: Arc<dyn Db>,
+    ) -> Result<(Measurements, Vec<StepResult>)> {
+        let measurements = self.run_measurements();
+        let schedule = self.props.get_target_schedule()?;
+        let start_time = Instant::now();
+        let mut runner = ClientRunner::new("ycsb-run")
+            .pin_cpus(self.pin_cpus)
+            .shutdown_signal(self.shutdown.clone());
+        for i in 0..self.threads {
+            let workload = workload.init_client(i, self.threads, measurements.clone())?;
+            let client = Client::new(db.clone(), workload, measurements.clone());
+            let mut throttle = Throttle::new(schedule.clone(), self.threads, start_time);
+            let op_count = split_ops(self.props.get_operation_count(), self.threads, i);
+            runner.spawn(move |shutdown| {
+                for _ in 0..op_count {
+                    if shutdown.is_shutdown() {
+                        break;
+                    }
+                    let delay = throttle.delay();
+                    if delay > Duration::default() {
+                        thread::sleep(delay);
+                    }
+                    let _ = client.do_transaction();
+                }
+            })?;
+        }
+        let clients = async move { tokio::task::spawn_blocking(move || runner.join()).await? };
+        let clients = wait_steps(clients, measurements.clone(), schedule, start_time);
+        let steps = self
+            .wait_clients(clients, &measurements, self.props.get_operation_count())
+            .await?;
+        Ok((measurements, steps))
+    }
+
+    async fn load_async(
+        &self,
+        workload: &dyn Workload,
+        db: Arc<dyn AsyncDb>,
+    ) -> Result<Measurements> {
+        let measurements = Measurements::new();
+        let batch_size = self.props.get_batch_size()?;
+        let concurrency = self.props.get_concurrency();
+        let mut handles = Vec::with_capacity(self.threads);
+        for i in 0..self.threads {
+            let workload = workload.init_client(i, self.threads, measurements.clone())?;
+            let client = AsyncClient::new(db.clone(), workload, measurements.clone());
+            let record_count = split_ops(self.props.get_insert_count(), self.threads, i);
+            let shutdown = self.shutdown.clone();
+            handles.push(tokio::spawn(async move {
+                let client = &client;
+                stream::iter(batches(record_count, batch_size))
+                    .take_while(|_| future::ready(!shutdown.is_shutdown()))
+                    .for_each_concurrent(concurrency, |count| async move {
+                        let _ = client.do_insert(count).await;
+                    })
+                    .await
+            }));
+        }
+        let clients = join_tasks(handles);
+        self.wait_clients(clients, &measurements, self.props.get_insert_count())
+            .await?;
+        Ok(measurements)
+    }
+
+    async fn run_async(
+        &self,
+        workload: &dyn Workload,
+        db: Arc<dyn AsyncDb>,
+    ) -> Result<(Measurements, Vec<StepResult>)> {
+        let measurements = self.run_measurements();
+        let concurrency = self.props.get_concurrency();
+        let schedule = self.props.get_target_schedule()?;
+        let start_time = Instant::now();
+        let mut handles = Vec::with_capacity(self.threads);
+        for i in 0..self.threads {
+            let workload = workload.init_client(i, self.threads, measurements.clone())?;
+            let client = AsyncClient::new(db.clone(), workload, measurements.clone());
+            let shutdown = self.shutdown.clone();
+            let throttle = Mutex::new(Throttle::new(schedule.clone(), self.threads, start_time));
+            let op_count = split_ops(self.props.get_operation_count(), self.threads, i);
+            handles.push(tokio::spawn(async move {
+                stream::iter(0..op_count)
+                    .take_while(|_| future::ready(!shutdown.is_shutdown()))
+                    .for_each_concurrent(concurrency, |_| async {
+                        let delay = throttle.lock().unwrap().delay();
+                        if delay > Duration::default() {
+                            time::delay_for(delay).await;
+                        }
+                        let _ = client.do_transaction().await;
+                    })
+                    .await
+            }));
+        }
+        let clients = join_tasks(handles);
+        let clients = wait_steps(clients, measurements.clone(), schedule, start_time);
+        let steps = self
+            .wait_clients(clients, &measurements, self.props.get_operation_count())
+            .await?;
+        Ok((measurements, steps))
+    }
+
+    /// Runs the clients in open loop: every client issues its operations on
+    /// an arrival schedule independent of their completions, and measures
+    /// their latencies from the arrival times as `INTENDED-{op}`. Arrivals
+    /// finding `concurrency` requests already in flight are counted as
+    /// `UNISSUED`.
+    async fn run_open_loop(
+        &self,
+        workload: &dyn Workload,
+        db: Arc<dyn AsyncDb>,
+    ) -> Result<(Measurements, Vec<StepResult>)> {
+        let schedule = self.props.get_target_schedule()?;
+        if schedule.points().iter().any(|(_, target)| *target <= 0.0) {
+            return Err(anyhow!("open loop requires a positive target"));
+        }
+        let start_time = Instant::now();
+        let measurements = self.run_measurements();
+        let concurrency = self.props.get_concurrency();
+        let threads = self.threads;
+        let mut handles = Vec::with_capacity(threads);
+        for i in 0..threads {
+            let workload = workload.init_client(i, self.threads, measurements.clone())?;
+            let client = AsyncClient::new(db.clone(), workload, measurements.clone());
+            let arrivals = self.props.get_arrival_generator(1.0)?;
+            let schedule = schedule.clone();
+            let measurements = measurements.clone();
+            let shutdown = self.shutdown.clone();
+            let op_count = split_ops(self.props.get_operation_count(), threads, i);
+            handles.push(tokio::spawn(async move {
+                let mut in_flight = FuturesUnordered::new();
+                let mut arrival = start_time;
+                for _ in 0..op_count {
+                    if shutdown.is_shutdown() {
+                        break;
+                    }
+                    let rate = schedule.target_at(arrival - start_time) / threads as f64;
+                    arrival += Duration::from_secs_f64(arrivals.next() / rate);
+                    let mut delay = time::delay_until(time::Instant::from_std(arrival));
+                    loop {
+                        if in_flight.is_empty() {
+                            delay.await;
+                            break;
+                        }
+                        match future::select(delay, in_flight.next()).await {
+                            Either::Left(_) => break,
+                            Either::Right((_, d)) => delay = d,
+                        }
+                    }
+                    if in_flight.len() >= concurrency {
+                        measurements.count("UNISSUED");
+                        continue;
+                    }
+                    let (client, measurements) = (&client, &measurements);
+                    in_flight.push(async move {
+                        let (name, result) = client.do_transaction().await;
+                        let name = client.intended_name(name);
+                        measurements.measure(&name, arrival.elapsed(), result.is_ok());
+                    });
+                }
+                while in_flight.next().await.is_some() {}
+            }));
+        }
+        let clients = join_tasks(handles);
+        let clients = wait_steps(clients, measurements.clone(), schedule, start_time);
+        let steps = self
+            .wait_clients(clients, &measurements, self.props.get_operation_count())
+            .await?;
+        Ok((measurements, steps))
+    }
+}
+
+/// Splits `count` records into batches of at most `batch_size` records,
+/// returning the size of every batch.
+fn batches(count: u64, batch_size: u64) -> impl Iterator<Item = u64> {
+    (0..count)
+        .step_by(batch_size as usize)
+        .map(move |start| batch_size.min(count - start))
+}
+
+async fn join_tasks(handles: Vec<JoinHandle<()>>) -> Result<()> {
+    for handle in handles {
+        handle.await?;
+    }
+    Ok(())
+}
+
+/// Waits for the clients of the run phase, collecting the throughput and
+/// latencies of every step of the target schedule.
+async fn wait_steps<F>(
+    clients: F,
+    measurements: Measurements,
+    schedule: TargetSchedule,
+    start_time: Instant,
+) -> Result<Vec<StepResult>>
+where
+    F: Future<Output = Result<()>>,
+{
+    let points = schedule.points();
+    if points.len() < 2 {
+        return clients.await.map(|_| Vec::new());
+    }
+    let mut clients = Box::pin(clients);
+    let mut reporter = StatusReporter::new(measurements, 0);
+    let mut statuses = Vec::with_capacity(points.len());
+    let mut result = None;
+    for (time, _) in &points[1..] {
+        let delay = time::delay_until(time::Instant::from_std(start_time + *time));
+        match future::select(clients.as_mut(), delay).await {
+            Either::Left((r, _)) => {
+                result = Some(r);
+                break;
+            }
+            Either::Right(_) => statuses.push(reporter.report()),
+        }
+    }
+    match result {
+        Some(r) => r?,
+        None => clients.await?,
+    }
+    statuses.push(reporter.report());
+
+    Ok(statuses
+        .into_iter()
+        .enumerate()
+        .map(|(i, status)| {
+            let (start, start_target) = points[i];
+            let (end, end_target) = match points.get(i + 1) {
+                Some((end, target)) if schedule.is_linear() => (Some(*end), *target),
+                Some((end, _)) => (Some(*end), start_target),
+                None => (None, start_target),
+            };
+            StepResult {
+                start,
+                end,
+                start_target,
+                end_target,
+                status,
+            }
+        })
+        .collect())
+}
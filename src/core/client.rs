@@ -1,67 +1,85 @@
+use std::borrow::Cow;
 use std::sync::Arc;
 
 use anyhow::Result;
 
-use super::db::Db;
-use super::workload::{CoreWorkload, Operation};
+use super::db::{AsyncDb, AsyncDbWrapper, Db, DbWrapper};
+use super::measurement::Measurements;
+use super::workload::WorkloadClient;
 
+/// Runs the operations of a workload against a `Db`. Every call to the `Db`
+/// is measured by a `DbWrapper`, and every finished operation is counted.
 pub struct Client {
-    db: Arc<dyn Db>,
-    workload: CoreWorkload,
+    db: DbWrapper,
+    workload: Box<dyn WorkloadClient>,
+    measurements: Measurements,
 }
 
 impl Client {
-    pub fn new(db: Arc<dyn Db>, workload: CoreWorkload) -> Self {
-        Self { db, workload }
+    pub fn new(
+        db: Arc<dyn Db>,
+        workload: Box<dyn WorkloadClient>,
+        measurements: Measurements,
+    ) -> Self {
+        Self {
+            db: DbWrapper::new(db, measurements.clone()),
+            workload,
+            measurements,
+        }
+    }
+
+    /// Inserts the next `count` records of the load phase.
+    pub fn do_insert(&self, count: u64) -> Result<()> {
+        let result = self.workload.do_insert(&self.db, count);
+        self.measurements.finish_operations(count);
+        result
     }
 
-    pub fn do_insert(&self) -> Result<()> {
-        let key = self.workload.next_sequence_key();
-        let values = self.workload.build_values();
-        self.db.insert(self.workload.next_table(), key, values)
+    /// Runs the next operation of the run phase, returning its name and
+    /// result.
+    pub fn do_transaction(&self) -> (&'static str, Result<()>) {
+        let result = self.workload.do_transaction(&self.db);
+        self.measurements.finish_operation();
+        result
     }
+}
 
-    pub fn do_transaction(&self) -> Result<()> {
-        let table = self.workload.next_table();
+/// The `Client` of an `AsyncDb`. Its operations can be polled concurrently
+/// to keep several requests in flight.
+pub struct AsyncClient {
+    db: AsyncDbWrapper,
+    workload: Box<dyn WorkloadClient>,
+    measurements: Measurements,
+}
 
-        match self.workload.next_operation() {
-            Operation::Read => {
-                let key = self.workload.next_transaction_key();
-                self.db
-                    .read(table, key, self.workload.read_fields())
-                    .map(|_| ())
-            }
-            Operation::Update => {
-                let key = self.workload.next_transaction_key();
-                let values = if self.workload.write_all_fields() {
-                    self.workload.build_values()
-                } else {
-                    self.workload.build_update()
-                };
-                self.db.update(table, key, values)
-            }
-            Operation::Insert => {
-                let key = self.workload.next_sequence_key();
-                self.db.insert(table, key, self.workload.build_values())
-            }
-            Operation::Scan => {
-                let key = self.workload.next_transaction_key();
-                let count = self.workload.next_scan_length();
-                self.db
-                    .scan(table, key, self.workload.read_fields(), count)
-                    .map(|_| ())
-            }
-            Operation::ReadModifyWrite => {
-                let key = self.workload.next_transaction_key();
-                self.db
-                    .read(table.clone(), key.clone(), self.workload.read_fields())?;
-                let values = if self.workload.write_all_fields() {
-                    self.workload.build_values()
-                } else {
-                    self.workload.build_update()
-                };
-                self.db.update(table, key, values)
-            }
+impl AsyncClient {
+    pub fn new(
+        db: Arc<dyn AsyncDb>,
+        workload: Box<dyn WorkloadClient>,
+        measurements: Measurements,
+    ) -> Self {
+        Self {
+            db: AsyncDbWrapper::new(db, measurements.clone()),
+            workload,
+            measurements,
         }
     }
+
+    pub async fn do_insert(&self, count: u64) -> Result<()> {
+        let result = self.workload.do_insert_async(&self.db, count).await;
+        self.measurements.finish_operations(count);
+        result
+    }
+
+    pub async fn do_transaction(&self) -> (&'static str, Result<()>) {
+        let result = self.workload.do_transaction_async(&self.db).await;
+        self.measurements.finish_operation();
+        result
+    }
+
+    /// Returns the name of the operation `name` measured from its intended
+    /// start, see `WorkloadClient::intended_name`.
+    pub fn intended_name(&self, name: &'static str) -> Cow<'static, str> {
+        self.workload.intended_name(name)
+    }
 }
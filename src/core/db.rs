@@ -1,6 +1,16 @@
+use std::sync::Arc;
+use std::time::Instant;
+
 use anyhow::Result;
+use futures::future::{self, BoxFuture, FutureExt};
+
+use super::measurement::Measurements;
+
+/// A field and its value.
+pub type KvPair = (String, Vec<u8>);
 
-pub type KvPair = (String, String);
+/// The key of a record and its fields.
+pub type Record = (Vec<u8>, Vec<KvPair>);
 
 pub trait Db: Send + Sync {
     ///
@@ -14,6 +24,16 @@ pub trait Db: Send + Sync {
     ///
     fn close(&self);
     ///
+    /// Starts a transaction, whose calls are made on the returned
+    /// `Transaction` until it is committed or aborted, so that the
+    /// transactions of several clients can run at once.
+    /// The default implementation returns `None`, for DBs without
+    /// transactions, whose calls are then made on the DB itself.
+    ///
+    fn start(&self) -> Result<Option<Arc<dyn Transaction>>> {
+        Ok(None)
+    }
+    ///
     /// Reads a record from the database.
     /// Field/value pairs from the result are stored in a vector.
     ///
@@ -22,7 +42,7 @@ pub trait Db: Send + Sync {
     /// @param fields The list of fields to read, or be empty for all of them.
     /// @return Ok(KV) on success, or an Err on error/record-miss.
     ///
-    fn read(&self, table: String, key: String, fields: Vec<String>) -> Result<Vec<KvPair>>;
+    fn read(&self, table: &str, key: &[u8], fields: &[String]) -> Result<Vec<KvPair>>;
     ///
     /// Performs a range scan for a set of records in the database.
     /// Field/value pairs from the result are stored in a vector.
@@ -31,15 +51,9 @@ pub trait Db: Send + Sync {
     /// @param key The key of the first record to read.
     /// @param record_count The number of records to read.
     /// @param fields The list of fields to read, or NULL for all of them.
-    /// @return Ok(Vec<KV)> on success, or an Err on error/record-miss.
+    /// @return Ok(Vec<(key, KV)>) in the order of the keys on success, or an Err on error/record-miss.
     ///
-    fn scan(
-        &self,
-        table: String,
-        key: String,
-        fields: Vec<String>,
-        count: u64,
-    ) -> Result<Vec<Vec<KvPair>>>;
+    fn scan(&self, table: &str, key: &[u8], fields: &[String], count: u64) -> Result<Vec<Record>>;
     ///
     /// Updates a record in the database.
     /// Field/value pairs in the specified vector are written to the record,
@@ -50,7 +64,7 @@ pub trait Db: Send + Sync {
     /// @param values A vector of field/value pairs to update in the record.
     /// @return Ok() on success, or an Err on error/record-miss.
     ///
-    fn update(&self, table: String, key: String, values: Vec<KvPair>) -> Result<()>;
+    fn update(&self, table: &str, key: &[u8], values: &[KvPair]) -> Result<()>;
     ///
     /// Inserts a record into the database.
     /// Field/value pairs in the specified vector are written into the record.
@@ -60,5 +74,592 @@ pub trait Db: Send + Sync {
     /// @param values A vector of field/value pairs to insert in the record.
     /// @return Ok() on success, or an Err on error/record-miss.
     ///
-    fn insert(&self, table: String, key: String, values: Vec<KvPair>) -> Result<()>;
+    fn insert(&self, table: &str, key: &[u8], values: &[KvPair]) -> Result<()>;
+    ///
+    /// Deletes a record from the database.
+    /// The default implementation returns an Err, for DBs without deletes.
+    ///
+    /// @param table The name of the table.
+    /// @param key The key of the record to delete.
+    /// @return Ok() on success, or an Err on error/record-miss.
+    ///
+    fn delete(&self, _: &str, _: &[u8]) -> Result<()> {
+        Err(anyhow!("delete not supported"))
+    }
+    ///
+    /// Inserts a batch of records into the database.
+    /// The default implementation inserts the records one by one.
+    ///
+    /// @param table The name of the table.
+    /// @param records A vector of keys and the field/value pairs to insert in their records.
+    /// @return Ok() on success, or an Err on error of any record.
+    ///
+    fn batch_insert(&self, table: &str, records: &[Record]) -> Result<()> {
+        for (key, values) in records {
+            self.insert(table, key, values)?;
+        }
+        Ok(())
+    }
+    ///
+    /// Reads a batch of records from the database.
+    /// The default implementation reads the records one by one.
+    ///
+    /// @param table The name of the table.
+    /// @param keys The keys of the records to read.
+    /// @param fields The list of fields to read, or be empty for all of them.
+    /// @return Ok(Vec<KV>) in the order of the keys on success, or an Err on error/record-miss.
+    ///
+    fn multi_get(
+        &self,
+        table: &str,
+        keys: &[Vec<u8>],
+        fields: &[String],
+    ) -> Result<Vec<Vec<KvPair>>> {
+        keys.iter()
+            .map(|key| self.read(table, key, fields))
+            .collect()
+    }
+}
+
+///
+/// A transaction started by `Db::start`. Its calls as a `Db` run in the
+/// transaction.
+///
+pub trait Transaction: Db {
+    ///
+    /// Returns the transaction as a `Db`, usually `self`.
+    ///
+    fn as_db(&self) -> &dyn Db;
+    ///
+    /// Commits the transaction.
+    ///
+    /// @return Ok() on success, or an Err if the transaction was aborted.
+    ///
+    fn commit(&self) -> Result<()>;
+    ///
+    /// Aborts the transaction.
+    ///
+    fn abort(&self) -> Result<()>;
+}
+
+///
+/// The non-blocking version of `Db`, for bindings whose requests can be kept
+/// in flight without occupying a thread.
+///
+pub trait AsyncDb: Send + Sync {
+    ///
+    /// Initializes any state for accessing this DB.
+    ///
+    fn init(&self);
+    ///
+    /// Clears any state for accessing this DB.
+    ///
+    fn close(&self);
+    ///
+    /// Starts a transaction, see `Db::start`.
+    ///
+    fn start(&self) -> BoxFuture<'_, Result<Option<Arc<dyn AsyncTransaction>>>> {
+        future::ready(Ok(None)).boxed()
+    }
+    ///
+    /// Reads a record from the database, see `Db::read`.
+    ///
+    fn read<'a>(
+        &'a self,
+        table: &'a str,
+        key: &'a [u8],
+        fields: &'a [String],
+    ) -> BoxFuture<'a, Result<Vec<KvPair>>>;
+    ///
+    /// Performs a range scan for a set of records in the database, see `Db::scan`.
+    ///
+    fn scan<'a>(
+        &'a self,
+        table: &'a str,
+        key: &'a [u8],
+        fields: &'a [String],
+        count: u64,
+    ) -> BoxFuture<'a, Result<Vec<Record>>>;
+    ///
+    /// Updates a record in the database, see `Db::update`.
+    ///
+    fn update<'a>(
+        &'a self,
+        table: &'a str,
+        key: &'a [u8],
+        values: &'a [KvPair],
+    ) -> BoxFuture<'a, Result<()>>;
+    ///
+    /// Inserts a record into the database, see `Db::insert`.
+    ///
+    fn insert<'a>(
+        &'a self,
+        table: &'a str,
+        key: &'a [u8],
+        values: &'a [KvPair],
+    ) -> BoxFuture<'a, Result<()>>;
+    ///
+    /// Deletes a record from the database, see `Db::delete`.
+    ///
+    fn delete<'a>(&'a self, _: &'a str, _: &'a [u8]) -> BoxFuture<'a, Result<()>> {
+        future::ready(Err(anyhow!("delete not supported"))).boxed()
+    }
+    ///
+    /// Inserts a batch of records into the database, see `Db::batch_insert`.
+    ///
+    fn batch_insert<'a>(
+        &'a self,
+        table: &'a str,
+        records: &'a [Record],
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            for (key, values) in records {
+                self.insert(table, key, values).await?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+    ///
+    /// Reads a batch of records from the database, see `Db::multi_get`.
+    ///
+    fn multi_get<'a>(
+        &'a self,
+        table: &'a str,
+        keys: &'a [Vec<u8>],
+        fields: &'a [String],
+    ) -> BoxFuture<'a, Result<Vec<Vec<KvPair>>>> {
+        async move {
+            let mut records = Vec::with_capacity(keys.len());
+            for key in keys {
+                records.push(self.read(table, key, fields).await?);
+            }
+            Ok(records)
+        }
+        .boxed()
+    }
+}
+
+///
+/// A transaction started by `AsyncDb::start`, see `Transaction`.
+///
+pub trait AsyncTransaction: AsyncDb {
+    ///
+    /// Returns the transaction as an `AsyncDb`, usually `self`.
+    ///
+    fn as_db(&self) -> &dyn AsyncDb;
+    ///
+    /// Commits the transaction, see `Transaction::commit`.
+    ///
+    fn commit(&self) -> BoxFuture<'_, Result<()>>;
+    ///
+    /// Aborts the transaction, see `Transaction::abort`.
+    ///
+    fn abort(&self) -> BoxFuture<'_, Result<()>>;
+}
+
+///
+/// Adapts a synchronous `Db` to `AsyncDb` by running every call on the
+/// blocking thread pool of the runtime. The borrowed arguments of a call are
+/// copied, since the blocking thread may outlive them. A `Transaction` is
+/// adapted the same way.
+///
+pub struct SyncDbAdapter<D: ?Sized = dyn Db> {
+    db: Arc<D>,
+}
+
+impl<D: Db + ?Sized + 'static> SyncDbAdapter<D> {
+    pub fn new(db: Arc<D>) -> Self {
+        Self { db }
+    }
+
+    fn blocking<T, F>(&self, f: F) -> BoxFuture<'static, Result<T>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&D) -> Result<T> + Send + 'static,
+    {
+        let db = self.db.clone();
+        async move { tokio::task::spawn_blocking(move || f(db.as_ref())).await? }.boxed()
+    }
+}
+
+impl<D: Db + ?Sized + 'static> AsyncDb for SyncDbAdapter<D> {
+    fn init(&self) {
+        self.db.init()
+    }
+
+    fn close(&self) {
+        self.db.close()
+    }
+
+    fn start(&self) -> BoxFuture<'_, Result<Option<Arc<dyn AsyncTransaction>>>> {
+        let start = self.blocking(|db| db.start());
+        async move {
+            let transaction = start.await?;
+            Ok(transaction.map(|t| Arc::new(SyncDbAdapter::new(t)) as Arc<dyn AsyncTransaction>))
+        }
+        .boxed()
+    }
+
+    fn read<'a>(
+        &'a self,
+        table: &'a str,
+        key: &'a [u8],
+        fields: &'a [String],
+    ) -> BoxFuture<'a, Result<Vec<KvPair>>> {
+        let (table, key, fields) = (table.to_string(), key.to_vec(), fields.to_vec());
+        self.blocking(move |db| db.read(&table, &key, &fields))
+    }
+
+    fn scan<'a>(
+        &'a self,
+        table: &'a str,
+        key: &'a [u8],
+        fields: &'a [String],
+        count: u64,
+    ) -> BoxFuture<'a, Result<Vec<Record>>> {
+        let (table, key, fields) = (table.to_string(), key.to_vec(), fields.to_vec());
+        self.blocking(move |db| db.scan(&table, &key, &fields, count))
+    }
+
+    fn update<'a>(
+        &'a self,
+        table: &'a str,
+        key: &'a [u8],
+        values: &'a [KvPair],
+    ) -> BoxFuture<'a, Result<()>> {
+        let (table, key, values) = (table.to_string(), key.to_vec(), values.to_vec());
+        self.blocking(move |db| db.update(&table, &key, &values))
+    }
+
+    fn insert<'a>(
+        &'a self,
+        table: &'a str,
+        key: &'a [u8],
+        values: &'a [KvPair],
+    ) -> BoxFuture<'a, Result<()>> {
+        let (table, key, values) = (table.to_string(), key.to_vec(), values.to_vec());
+        self.blocking(move |db| db.insert(&table, &key, &values))
+    }
+
+    fn delete<'a>(&'a self, table: &'a str, key: &'a [u8]) -> BoxFuture<'a, Result<()>> {
+        let (table, key) = (table.to_string(), key.to_vec());
+        self.blocking(move |db| db.delete(&table, &key))
+    }
+
+    fn batch_insert<'a>(
+        &'a self,
+        table: &'a str,
+        records: &'a [Record],
+    ) -> BoxFuture<'a, Result<()>> {
+        let (table, records) = (table.to_string(), records.to_vec());
+        self.blocking(move |db| db.batch_insert(&table, &records))
+    }
+
+    fn multi_get<'a>(
+        &'a self,
+        table: &'a str,
+        keys: &'a [Vec<u8>],
+        fields: &'a [String],
+    ) -> BoxFuture<'a, Result<Vec<Vec<KvPair>>>> {
+        let (table, keys, fields) = (table.to_string(), keys.to_vec(), fields.to_vec());
+        self.blocking(move |db| db.multi_get(&table, &keys, &fields))
+    }
+}
+
+impl AsyncTransaction for SyncDbAdapter<dyn Transaction> {
+    fn as_db(&self) -> &dyn AsyncDb {
+        self
+    }
+
+    fn commit(&self) -> BoxFuture<'_, Result<()>> {
+        self.blocking(|transaction| transaction.commit())
+    }
+
+    fn abort(&self) -> BoxFuture<'_, Result<()>> {
+        self.blocking(|transaction| transaction.abort())
+    }
+}
+
+///
+/// Wraps a `Db` to measure the latency and status of every call, recorded
+/// under the name of the call, e.g. `READ`. A batch call is recorded as a
+/// whole, e.g. `BATCH-INSERT`, and its latency shared by its records is
+/// recorded once per record, e.g. under `INSERT`. The transactions started
+/// by the `Db` are wrapped too.
+///
+pub struct DbWrapper<D: ?Sized = dyn Db> {
+    db: Arc<D>,
+    measurements: Measurements,
+}
+
+impl<D: Db + ?Sized> DbWrapper<D> {
+    pub fn new(db: Arc<D>, measurements: Measurements) -> Self {
+        Self { db, measurements }
+    }
+
+    /// Returns the wrapped `Db`, whose calls are not measured.
+    pub fn db(&self) -> &D {
+        self.db.as_ref()
+    }
+
+    fn measured<T, F>(&self, name: &str, f: F) -> Result<T>
+    where
+        F: FnOnce(&D) -> Result<T>,
+    {
+        let start = Instant::now();
+        let result = f(self.db.as_ref());
+        self.measurements
+            .measure(name, start.elapsed(), result.is_ok());
+        result
+    }
+
+    fn measured_batch<T, F>(&self, name: &str, item_name: &str, items: usize, f: F) -> Result<T>
+    where
+        F: FnOnce(&D) -> Result<T>,
+    {
+        let start = Instant::now();
+        let result = f(self.db.as_ref());
+        let latency = start.elapsed();
+        self.measurements.measure(name, latency, result.is_ok());
+        self.measurements
+            .measure_batch(item_name, latency, items, result.is_ok());
+        result
+    }
+}
+
+impl<D: Db + ?Sized> Db for DbWrapper<D> {
+    fn init(&self) {
+        self.db.init()
+    }
+
+    fn close(&self) {
+        self.db.close()
+    }
+
+    fn start(&self) -> Result<Option<Arc<dyn Transaction>>> {
+        let transaction = self.measured("START", |db| db.start())?;
+        Ok(transaction.map(|t| {
+            Arc::new(DbWrapper::new(t, self.measurements.clone())) as Arc<dyn Transaction>
+        }))
+    }
+
+    fn read(&self, table: &str, key: &[u8], fields: &[String]) -> Result<Vec<KvPair>> {
+        self.measured("READ", |db| db.read(table, key, fields))
+    }
+
+    fn scan(&self, table: &str, key: &[u8], fields: &[String], count: u64) -> Result<Vec<Record>> {
+        self.measured("SCAN", |db| db.scan(table, key, fields, count))
+    }
+
+    fn update(&self, table: &str, key: &[u8], values: &[KvPair]) -> Result<()> {
+        self.measured("UPDATE", |db| db.update(table, key, values))
+    }
+
+    fn insert(&self, table: &str, key: &[u8], values: &[KvPair]) -> Result<()> {
+        self.measured("INSERT", |db| db.insert(table, key, values))
+    }
+
+    fn delete(&self, table: &str, key: &[u8]) -> Result<()> {
+        self.measured("DELETE", |db| db.delete(table, key))
+    }
+
+    fn batch_insert(&self, table: &str, records: &[Record]) -> Result<()> {
+        let items = records.len();
+        self.measured_batch("BATCH-INSERT", "INSERT", items, |db| {
+            db.batch_insert(table, records)
+        })
+    }
+
+    fn multi_get(
+        &self,
+        table: &str,
+        keys: &[Vec<u8>],
+        fields: &[String],
+    ) -> Result<Vec<Vec<KvPair>>> {
+        let items = keys.len();
+        self.measured_batch("MULTI-GET", "READ", items, |db| {
+            db.multi_get(table, keys, fields)
+        })
+    }
+}
+
+impl Transaction for DbWrapper<dyn Transaction> {
+    fn as_db(&self) -> &dyn Db {
+        self
+    }
+
+    fn commit(&self) -> Result<()> {
+        self.measured("COMMIT", |transaction| transaction.commit())
+    }
+
+    fn abort(&self) -> Result<()> {
+        self.measured("ABORT", |transaction| transaction.abort())
+    }
+}
+
+///
+/// The `DbWrapper` of an `AsyncDb`, measuring every call from its first poll
+/// until it completes.
+///
+pub struct AsyncDbWrapper<D: ?Sized = dyn AsyncDb> {
+    db: Arc<D>,
+    measurements: Measurements,
+}
+
+impl<D: AsyncDb + ?Sized> AsyncDbWrapper<D> {
+    pub fn new(db: Arc<D>, measurements: Measurements) -> Self {
+        Self { db, measurements }
+    }
+
+    /// Returns the wrapped `AsyncDb`, whose calls are not measured.
+    pub fn db(&self) -> &D {
+        self.db.as_ref()
+    }
+
+    fn measured<'a, T>(
+        &'a self,
+        name: &'static str,
+        f: BoxFuture<'a, Result<T>>,
+    ) -> BoxFuture<'a, Result<T>>
+    where
+        T: Send + 'a,
+    {
+        async move {
+            let start = Instant::now();
+            let result = f.await;
+            self.measurements
+                .measure(name, start.elapsed(), result.is_ok());
+            result
+        }
+        .boxed()
+    }
+
+    fn measured_batch<'a, T>(
+        &'a self,
+        name: &'static str,
+        item_name: &'static str,
+        items: usize,
+        f: BoxFuture<'a, Result<T>>,
+    ) -> BoxFuture<'a, Result<T>>
+    where
+        T: Send + 'a,
+    {
+        async move {
+            let start = Instant::now();
+            let result = f.await;
+            let latency = start.elapsed();
+            self.measurements.measure(name, latency, result.is_ok());
+            self.measurements
+                .measure_batch(item_name, latency, items, result.is_ok());
+            result
+        }
+        .boxed()
+    }
+}
+
+impl<D: AsyncDb + ?Sized> AsyncDb for AsyncDbWrapper<D> {
+    fn init(&self) {
+        self.db.init()
+    }
+
+    fn close(&self) {
+        self.db.close()
+    }
+
+    fn start(&self) -> BoxFuture<'_, Result<Option<Arc<dyn AsyncTransaction>>>> {
+        let start = self.measured("START", self.db.start());
+        async move {
+            let transaction = start.await?;
+            Ok(transaction.map(|t| {
+                Arc::new(AsyncDbWrapper::new(t, self.measurements.clone()))
+                    as Arc<dyn AsyncTransaction>
+            }))
+        }
+        .boxed()
+    }
+
+    fn read<'a>(
+        &'a self,
+        table: &'a str,
+        key: &'a [u8],
+        fields: &'a [String],
+    ) -> BoxFuture<'a, Result<Vec<KvPair>>> {
+        self.measured("READ", self.db.read(table, key, fields))
+    }
+
+    fn scan<'a>(
+        &'a self,
+        table: &'a str,
+        key: &'a [u8],
+        fields: &'a [String],
+        count: u64,
+    ) -> BoxFuture<'a, Result<Vec<Record>>> {
+        self.measured("SCAN", self.db.scan(table, key, fields, count))
+    }
+
+    fn update<'a>(
+        &'a self,
+        table: &'a str,
+        key: &'a [u8],
+        values: &'a [KvPair],
+    ) -> BoxFuture<'a, Result<()>> {
+        self.measured("UPDATE", self.db.update(table, key, values))
+    }
+
+    fn insert<'a>(
+        &'a self,
+        table: &'a str,
+        key: &'a [u8],
+        values: &'a [KvPair],
+    ) -> BoxFuture<'a, Result<()>> {
+        self.measured("INSERT", self.db.insert(table, key, values))
+    }
+
+    fn delete<'a>(&'a self, table: &'a str, key: &'a [u8]) -> BoxFuture<'a, Result<()>> {
+        self.measured("DELETE", self.db.delete(table, key))
+    }
+
+    fn batch_insert<'a>(
+        &'a self,
+        table: &'a str,
+        records: &'a [Record],
+    ) -> BoxFuture<'a, Result<()>> {
+        let items = records.len();
+        self.measured_batch(
+            "BATCH-INSERT",
+            "INSERT",
+            items,
+            self.db.batch_insert(table, records),
+        )
+    }
+
+    fn multi_get<'a>(
+        &'a self,
+        table: &'a str,
+        keys: &'a [Vec<u8>],
+        fields: &'a [String],
+    ) -> BoxFuture<'a, Result<Vec<Vec<KvPair>>>> {
+        let items = keys.len();
+        self.measured_batch(
+            "MULTI-GET",
+            "READ",
+            items,
+            self.db.multi_get(table, keys, fields),
+        )
+    }
+}
+
+impl AsyncTransaction for AsyncDbWrapper<dyn AsyncTransaction> {
+    fn as_db(&self) -> &dyn AsyncDb {
+        self
+    }
+
+    fn commit(&self) -> BoxFuture<'_, Result<()>> {
+        self.measured("COMMIT", self.db.commit())
+    }
+
+    fn abort(&self) -> BoxFuture<'_, Result<()>> {
+        self.measured("ABORT", self.db.abort())
+    }
 }
@@ -1,13 +1,19 @@
+mod acknowledged;
 mod constant;
 mod counter;
 mod discrete;
+mod exponential;
 mod uniform;
+mod value;
 mod zipfian;
 
+pub use acknowledged::*;
 pub use constant::*;
 pub use counter::*;
 pub use discrete::*;
+pub use exponential::*;
 pub use uniform::*;
+pub use value::*;
 pub use zipfian::*;
 
 pub trait Generator<T>: Send + Sync {
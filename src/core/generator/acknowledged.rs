@@ -0,0 +1,82 @@
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use super::{CounterGenerator, Generator};
+
+/// A `CounterGenerator` whose `last` only advances over the values that were
+/// acknowledged, so that a value is never returned by `last` before all the
+/// values below it were acknowledged. A value that was not used can be
+/// released instead, to be returned by `next` again.
+pub struct AcknowledgedCounterGenerator {
+    counter: CounterGenerator,
+    limit: AtomicU64,
+    acknowledged: Mutex<BTreeSet<u64>>,
+    released: Mutex<Vec<u64>>,
+}
+
+impl AcknowledgedCounterGenerator {
+    pub fn new(start: u64) -> Self {
+        Self {
+            counter: CounterGenerator::new(start),
+            limit: AtomicU64::new(start),
+            acknowledged: Mutex::new(BTreeSet::new()),
+            released: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Acknowledges `value`, which was returned by `next`.
+    pub fn acknowledge(&self, value: u64) {
+        let mut acknowledged = self.acknowledged.lock().unwrap();
+        acknowledged.insert(value);
+        let mut limit = self.limit.load(Ordering::Acquire);
+        while acknowledged.remove(&limit) {
+            limit += 1;
+        }
+        self.limit.store(limit, Ordering::Release);
+    }
+
+    /// Releases `value`, which was returned by `next` but not used, so that
+    /// `next` returns it again before any new value. `last` does not advance
+    /// over `value` until it is acknowledged.
+    pub fn release(&self, value: u64) {
+        self.released.lock().unwrap().push(value);
+    }
+}
+
+impl Generator<u64> for AcknowledgedCounterGenerator {
+    fn next(&self) -> u64 {
+        match self.released.lock().unwrap().pop() {
+            Some(value) => value,
+            None => self.counter.next(),
+        }
+    }
+
+    /// Returns the highest value below which all the values were
+    /// acknowledged. Panics if the counter started at 0 and none was
+    /// acknowledged yet.
+    fn last(&self) -> u64 {
+        self.limit
+            .load(Ordering::Acquire)
+            .checked_sub(1)
+            .expect("no value was acknowledged")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release() {
+        let generator = AcknowledgedCounterGenerator::new(10);
+        let (first, second) = (generator.next(), generator.next());
+        generator.acknowledge(second);
+        generator.release(first);
+        assert_eq!(generator.limit.load(Ordering::Acquire), 10);
+        assert_eq!(generator.next(), first);
+        generator.acknowledge(first);
+        assert_eq!(generator.last(), second);
+        assert_eq!(generator.next(), 12);
+    }
+}
@@ -0,0 +1,33 @@
+use crossbeam::atomic::AtomicCell;
+use rand::{self, Rng};
+
+use super::Generator;
+
+/// Generates exponentially distributed values, e.g. the intervals between
+/// the arrivals of a Poisson process.
+pub struct ExponentialGenerator {
+    gamma: f64,
+    last_value: AtomicCell<f64>,
+}
+
+impl ExponentialGenerator {
+    pub fn new(mean: f64) -> Self {
+        assert!(mean > 0.0);
+        Self {
+            gamma: 1.0 / mean,
+            last_value: AtomicCell::new(0.0),
+        }
+    }
+}
+
+impl Generator<f64> for ExponentialGenerator {
+    fn next(&self) -> f64 {
+        let val = -(1.0 - rand::thread_rng().gen::<f64>()).ln() / self.gamma;
+        self.last_value.store(val);
+        val
+    }
+
+    fn last(&self) -> f64 {
+        self.last_value.load()
+    }
+}
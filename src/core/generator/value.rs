@@ -0,0 +1,146 @@
+use rand::{self, distributions, seq::SliceRandom, Rng, RngCore};
+
+/// A generator of the values of the fields of a record.
+pub trait ValueGenerator: Send + Sync {
+    /// Replaces `value` with a new value of `len` bytes, reusing its
+    /// allocation.
+    fn next_value(&self, value: &mut Vec<u8>, len: usize);
+}
+
+/// Random alphanumeric values, which are barely compressible.
+pub struct RandomValueGenerator;
+
+impl ValueGenerator for RandomValueGenerator {
+    fn next_value(&self, value: &mut Vec<u8>, len: usize) {
+        value.clear();
+        value.extend(
+            rand::thread_rng()
+                .sample_iter(distributions::Alphanumeric)
+                .take(len)
+                .map(|c| c as u8),
+        );
+    }
+}
+
+/// Values that compress to about `ratio` of their size: a random printable
+/// prefix of `ratio * len` bytes repeated up to `len` bytes.
+pub struct CompressibleValueGenerator {
+    ratio: f64,
+}
+
+impl CompressibleValueGenerator {
+    pub fn new(ratio: f64) -> Self {
+        assert!(ratio > 0.0 && ratio <= 1.0);
+        Self { ratio }
+    }
+}
+
+impl ValueGenerator for CompressibleValueGenerator {
+    fn next_value(&self, value: &mut Vec<u8>, len: usize) {
+        let raw_len = ((len as f64 * self.ratio).ceil() as usize).max(1).min(len);
+        let mut rng = rand::thread_rng();
+        value.clear();
+        value.extend((0..raw_len).map(|_| rng.gen_range(b' ', b'~' + 1)));
+        for i in raw_len..len {
+            value.push(value[i - raw_len]);
+        }
+    }
+}
+
+const DICTIONARY: &[&str] = &[
+    "the", "of", "and", "to", "in", "is", "was", "that", "for", "on", "with", "as", "by", "at",
+    "from", "his", "her", "which", "were", "are", "this", "be", "an", "had", "not", "or", "have",
+    "first", "one", "their", "its", "new", "after", "who", "they", "two", "been", "has", "also",
+    "would", "more", "there", "when", "into", "time", "other", "during", "some", "year", "city",
+    "people", "world", "system", "record", "value", "table", "field", "number", "state", "company",
+    "school", "between", "under", "while",
+];
+
+/// Values of random words of a small dictionary separated by spaces, which
+/// compress like plain text.
+pub struct DictionaryValueGenerator;
+
+impl ValueGenerator for DictionaryValueGenerator {
+    fn next_value(&self, value: &mut Vec<u8>, len: usize) {
+        let mut rng = rand::thread_rng();
+        value.clear();
+        while value.len() < len {
+            value.extend_from_slice(DICTIONARY.choose(&mut rng).unwrap().as_bytes());
+            value.push(b' ');
+        }
+        value.truncate(len);
+    }
+}
+
+/// Values of zero bytes only.
+pub struct ZeroValueGenerator;
+
+impl ValueGenerator for ZeroValueGenerator {
+    fn next_value(&self, value: &mut Vec<u8>, len: usize) {
+        value.clear();
+        value.resize(len, 0);
+    }
+}
+
+/// Values of uniformly random bytes, which are incompressible.
+pub struct BinaryValueGenerator;
+
+impl ValueGenerator for BinaryValueGenerator {
+    fn next_value(&self, value: &mut Vec<u8>, len: usize) {
+        value.resize(len, 0);
+        rand::thread_rng().fill_bytes(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LENS: [usize; 5] = [0, 1, 7, 100, 1000];
+
+    #[test]
+    fn test_value_len() {
+        let generators: Vec<Box<dyn ValueGenerator>> = vec![
+            Box::new(RandomValueGenerator),
+            Box::new(CompressibleValueGenerator::new(0.3)),
+            Box::new(DictionaryValueGenerator),
+            Box::new(ZeroValueGenerator),
+            Box::new(BinaryValueGenerator),
+        ];
+        for generator in &generators {
+            // Shorter values reuse the buffer of a longer one.
+            let mut value = vec![b'x'; 2000];
+            for &len in LENS.iter().rev().chain(LENS.iter()) {
+                generator.next_value(&mut value, len);
+                assert_eq!(value.len(), len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_zero_value() {
+        let mut value = vec![b'x'; 10];
+        for &len in &LENS {
+            ZeroValueGenerator.next_value(&mut value, len);
+            assert!(value.iter().all(|b| *b == 0));
+        }
+    }
+
+    #[test]
+    fn test_compressible_value() {
+        let mut value = Vec::new();
+        for &(ratio, len, prefix_len) in &[
+            (0.5, 100, 50),
+            (0.25, 1000, 250),
+            (0.3, 7, 3),
+            (0.01, 10, 1),
+            (1.0, 100, 100),
+        ] {
+            CompressibleValueGenerator::new(ratio).next_value(&mut value, len);
+            assert!(value.iter().all(|b| (b' '..=b'~').contains(b)));
+            for i in prefix_len..len {
+                assert_eq!(value[i], value[i - prefix_len], "{} {}", ratio, len);
+            }
+        }
+    }
+}
@@ -2,16 +2,22 @@ use anyhow::Result;
 
 use std::collections::HashMap;
 use std::io::BufRead;
+use std::time::Duration;
 
 use super::generator::*;
+use super::schedule::TargetSchedule;
 use super::workload::Operation;
 
+pub const WORKLOAD_PROPERTY: &str = "workload";
 pub const TABLENAME_PROPERTY: &str = "table";
 pub const FIELD_COUNT_PROPERTY: &str = "fieldcount";
 pub const FIELD_LENGTH_DISTRIBUTION_PROPERTY: &str = "field_len_dist";
 pub const FIELD_LENGTH_PROPERTY: &str = "fieldlength";
 pub const READ_ALL_FIELDS_PROPERTY: &str = "readallfields";
 pub const WRITE_ALL_FIELDS_PROPERTY: &str = "writeallfields";
+pub const DATA_INTEGRITY_PROPERTY: &str = "dataintegrity";
+pub const VALUE_GENERATOR_PROPERTY: &str = "valuegenerator";
+pub const COMPRESSIBILITY_PROPERTY: &str = "compressibility";
 pub const READ_PROPORTION_PROPERTY: &str = "readproportion";
 pub const UPDATE_PROPORTION_PROPERTY: &str = "updateproportion";
 pub const INSERT_PROPORTION_PROPERTY: &str = "insertproportion";
@@ -21,18 +27,39 @@ pub const REQUEST_DISTRIBUTION_PROPERTY: &str = "requestdistribution";
 pub const MAX_SCAN_LENGTH_PROPERTY: &str = "maxscanlength";
 pub const SCAN_LENGTH_DISTRIBUTION_PROPERTY: &str = "scanlengthdistribution";
 pub const INSERT_ORDER_PROPERTY: &str = "insertorder";
+pub const KEY_PREFIX_PROPERTY: &str = "keyprefix";
+pub const ZERO_PADDING_PROPERTY: &str = "zeropadding";
+pub const KEY_FORMAT_PROPERTY: &str = "keyformat";
 pub const INSERT_START_PROPERTY: &str = "insertstart";
+pub const INSERT_COUNT_PROPERTY: &str = "insertcount";
 pub const RECORD_COUNT_PROPERTY: &str = "recordcount";
 pub const OPERATION_COUNT_PROPERTY: &str = "operationcount";
+pub const WARMUP_TIME_PROPERTY: &str = "warmuptime";
+pub const WARMUP_OPS_PROPERTY: &str = "warmupops";
+pub const CONCURRENCY_PROPERTY: &str = "concurrency";
+pub const BATCH_SIZE_PROPERTY: &str = "batchsize";
+pub const READ_BATCH_SIZE_PROPERTY: &str = "readbatchsize";
+pub const TRANSACTION_SIZE_PROPERTY: &str = "transactionsize";
+pub const TRANSACTION_SIZE_DISTRIBUTION_PROPERTY: &str = "transactionsizedistribution";
+pub const TOTAL_CASH_PROPERTY: &str = "totalcash";
+pub const LOAD_MODE_PROPERTY: &str = "loadmode";
+pub const TARGET_PROPERTY: &str = "target";
+pub const ARRIVAL_DISTRIBUTION_PROPERTY: &str = "arrivaldistribution";
+pub const TARGET_SCHEDULE_PROPERTY: &str = "targetschedule";
+pub const TARGET_SCHEDULE_TYPE_PROPERTY: &str = "targetscheduletype";
 
 lazy_static! {
     static ref DEFAULT_PROPERTIES: HashMap<&'static str, &'static str> = [
+        (WORKLOAD_PROPERTY, "core"),
         (TABLENAME_PROPERTY, "usertable"),
         (FIELD_COUNT_PROPERTY, "10"),
         (FIELD_LENGTH_DISTRIBUTION_PROPERTY, "constant"),
         (FIELD_LENGTH_PROPERTY, "100"),
         (READ_ALL_FIELDS_PROPERTY, "true"),
         (WRITE_ALL_FIELDS_PROPERTY, "false"),
+        (DATA_INTEGRITY_PROPERTY, "false"),
+        (VALUE_GENERATOR_PROPERTY, "random"),
+        (COMPRESSIBILITY_PROPERTY, "0.5"),
         (READ_PROPORTION_PROPERTY, "0.95"),
         (UPDATE_PROPORTION_PROPERTY, "0.05"),
         (INSERT_PROPORTION_PROPERTY, "0.0"),
@@ -42,7 +69,23 @@ lazy_static! {
         (MAX_SCAN_LENGTH_PROPERTY, "1000"),
         (SCAN_LENGTH_DISTRIBUTION_PROPERTY, "uniform"),
         (INSERT_ORDER_PROPERTY, "hashed"),
+        (KEY_PREFIX_PROPERTY, "user"),
+        (ZERO_PADDING_PROPERTY, "1"),
+        (KEY_FORMAT_PROPERTY, "decimal"),
         (INSERT_START_PROPERTY, "0"),
+        (WARMUP_TIME_PROPERTY, "0"),
+        (WARMUP_OPS_PROPERTY, "0"),
+        (CONCURRENCY_PROPERTY, "1"),
+        (BATCH_SIZE_PROPERTY, "1"),
+        (READ_BATCH_SIZE_PROPERTY, "1"),
+        (TRANSACTION_SIZE_PROPERTY, "4"),
+        (TRANSACTION_SIZE_DISTRIBUTION_PROPERTY, "constant"),
+        (TOTAL_CASH_PROPERTY, "1000000"),
+        (LOAD_MODE_PROPERTY, "closed"),
+        (TARGET_PROPERTY, "0"),
+        (ARRIVAL_DISTRIBUTION_PROPERTY, "poisson"),
+        (TARGET_SCHEDULE_PROPERTY, ""),
+        (TARGET_SCHEDULE_TYPE_PROPERTY, "step"),
     ]
     .iter()
     .copied()
@@ -82,6 +125,10 @@ impl Properties {
             .expect(format!("property {} not found", key).as_str())
     }
 
+    pub fn set_property(&mut self, key: &str, value: String) {
+        self.inner.insert(key.to_string(), value);
+    }
+
     pub fn get_field_len_generator(&self) -> Result<Box<dyn Generator<u64>>> {
         let field_len_dist = self.get_property(FIELD_LENGTH_DISTRIBUTION_PROPERTY);
         let field_len: u64 = self.get_property(FIELD_LENGTH_PROPERTY).parse()?;
@@ -99,6 +146,27 @@ impl Properties {
         Ok(field_len_generator)
     }
 
+    /// Returns the generator of the values of the fields, selected by the
+    /// `valuegenerator` property.
+    pub fn get_value_generator(&self) -> Result<Box<dyn ValueGenerator>> {
+        let value_generator = self.get_property(VALUE_GENERATOR_PROPERTY);
+        let value_generator: Box<dyn ValueGenerator> = match value_generator.as_str() {
+            "random" => Box::new(RandomValueGenerator),
+            "compressible" => {
+                let ratio: f64 = self.get_property(COMPRESSIBILITY_PROPERTY).parse()?;
+                if ratio <= 0.0 || ratio > 1.0 {
+                    return Err(anyhow!("compressibility {} not in (0, 1]", ratio));
+                }
+                Box::new(CompressibleValueGenerator::new(ratio))
+            }
+            "dictionary" => Box::new(DictionaryValueGenerator),
+            "zeros" => Box::new(ZeroValueGenerator),
+            "binary" => Box::new(BinaryValueGenerator),
+            _ => return Err(anyhow!("unsupported value generator {}", value_generator)),
+        };
+        Ok(value_generator)
+    }
+
     pub fn get_request_generator(&self) -> Result<Box<dyn Generator<u64>>> {
         let request_dist = self.get_property(REQUEST_DISTRIBUTION_PROPERTY);
         let record_count: u64 = self.get_property(RECORD_COUNT_PROPERTY).parse()?;
@@ -137,6 +205,37 @@ impl Properties {
         Ok(scan_length_generator)
     }
 
+    /// Returns the generator of the number of operations in a transaction of
+    /// the transactional workload.
+    pub fn get_transaction_size_generator(&self) -> Result<Box<dyn Generator<u64>>> {
+        let size_dist = self.get_property(TRANSACTION_SIZE_DISTRIBUTION_PROPERTY);
+        let size: u64 = self.get_property(TRANSACTION_SIZE_PROPERTY).parse()?;
+        let size_generator: Box<dyn Generator<u64>> = match size_dist.as_str() {
+            "uniform" => Box::new(UniformGenerator::new().min(1).max(size)),
+            "zipfian" => Box::new(ZipfianGenerator::new().min(1).max(size)),
+            "constant" => Box::new(ConstantGenerator::new(size)),
+            _ => {
+                return Err(anyhow!(
+                    "unsupported transaction size distribution {}",
+                    size_dist
+                ))
+            }
+        };
+        Ok(size_generator)
+    }
+
+    /// Returns the generator of the seconds between two arrivals of an
+    /// open-loop client issuing `rate` operations per second.
+    pub fn get_arrival_generator(&self, rate: f64) -> Result<Box<dyn Generator<f64>>> {
+        let arrival_dist = self.get_property(ARRIVAL_DISTRIBUTION_PROPERTY);
+        let arrival_generator: Box<dyn Generator<f64>> = match arrival_dist.as_str() {
+            "poisson" => Box::new(ExponentialGenerator::new(1.0 / rate)),
+            "constant" => Box::new(ConstantGenerator::new(1.0 / rate)),
+            _ => return Err(anyhow!("unsupported arrival distribution {}", arrival_dist)),
+        };
+        Ok(arrival_generator)
+    }
+
     pub fn get_operation_generator(&self) -> Result<DiscreteGenerator<Operation>> {
         let read_proportion: f64 = self.get_property(READ_PROPORTION_PROPERTY).parse()?;
         let update_proportion: f64 = self.get_property(UPDATE_PROPORTION_PROPERTY).parse()?;
@@ -175,4 +274,118 @@ impl Properties {
             .parse()
             .expect("parse record count failed")
     }
+
+    /// Returns the minimum number of digits of a decimal key number, which
+    /// is padded with zeros up to it.
+    pub fn get_zero_padding(&self) -> usize {
+        self.get_property(ZERO_PADDING_PROPERTY)
+            .parse()
+            .expect("parse zero padding failed")
+    }
+
+    pub fn get_insert_start(&self) -> u64 {
+        self.get_property(INSERT_START_PROPERTY)
+            .parse()
+            .expect("parse insert start failed")
+    }
+
+    /// Returns the number of records this process inserts in the load phase,
+    /// which defaults to the rest of the key space after `insertstart`.
+    pub fn get_insert_count(&self) -> u64 {
+        match self.inner.get(INSERT_COUNT_PROPERTY) {
+            Some(count) => count.parse().expect("parse insert count failed"),
+            None => self
+                .get_record_count()
+                .saturating_sub(self.get_insert_start()),
+        }
+    }
+
+    /// Returns how long the run phase warms up before it is measured.
+    pub fn get_warmup_time(&self) -> Duration {
+        let secs = self
+            .get_property(WARMUP_TIME_PROPERTY)
+            .parse()
+            .expect("parse warmup time failed");
+        Duration::from_secs(secs)
+    }
+
+    /// Returns how many operations the run phase executes before it is measured.
+    pub fn get_warmup_ops(&self) -> u64 {
+        self.get_property(WARMUP_OPS_PROPERTY)
+            .parse()
+            .expect("parse warmup ops failed")
+    }
+
+    /// Returns how many requests every client keeps in flight. Values above
+    /// one run the clients on `AsyncDb`.
+    pub fn get_concurrency(&self) -> usize {
+        self.get_property(CONCURRENCY_PROPERTY)
+            .parse()
+            .expect("parse concurrency failed")
+    }
+
+    /// Returns how many records a client inserts in one `Db::batch_insert`
+    /// call during the load phase.
+    pub fn get_batch_size(&self) -> Result<u64> {
+        let batch_size = self.get_property(BATCH_SIZE_PROPERTY).parse()?;
+        if batch_size == 0 {
+            return Err(anyhow!("batch size must be positive"));
+        }
+        Ok(batch_size)
+    }
+
+    /// Returns how many records a read of the run phase reads in one
+    /// `Db::multi_get` call.
+    pub fn get_read_batch_size(&self) -> Result<u64> {
+        let batch_size = self.get_property(READ_BATCH_SIZE_PROPERTY).parse()?;
+        if batch_size == 0 {
+            return Err(anyhow!("read batch size must be positive"));
+        }
+        Ok(batch_size)
+    }
+
+    /// Returns the total balance of the accounts of the closed economy
+    /// workload.
+    pub fn get_total_cash(&self) -> u64 {
+        self.get_property(TOTAL_CASH_PROPERTY)
+            .parse()
+            .expect("parse total cash failed")
+    }
+
+    /// Returns whether operations arrive on a schedule independent of their
+    /// completions, rather than one after another.
+    pub fn is_open_loop(&self) -> Result<bool> {
+        let load_mode = self.get_property(LOAD_MODE_PROPERTY);
+        match load_mode.as_str() {
+            "closed" => Ok(false),
+            "open" => Ok(true),
+            _ => Err(anyhow!("unsupported load mode {}", load_mode)),
+        }
+    }
+
+    /// Returns the total operations per second of all clients, or zero when
+    /// the throughput is not limited.
+    pub fn get_target(&self) -> f64 {
+        self.get_property(TARGET_PROPERTY)
+            .parse()
+            .expect("parse target failed")
+    }
+
+    /// Returns the target throughput of the run phase over time, which is
+    /// `targetschedule` if set, or else the constant `target`.
+    pub fn get_target_schedule(&self) -> Result<TargetSchedule> {
+        let schedule = self.get_property(TARGET_SCHEDULE_PROPERTY);
+        if schedule.is_empty() {
+            return Ok(TargetSchedule::constant(self.get_target()));
+        }
+        let schedule_type = self.get_property(TARGET_SCHEDULE_TYPE_PROPERTY);
+        match schedule_type.as_str() {
+            "step" => TargetSchedule::parse(&schedule, false),
+            "linear" => TargetSchedule::parse(&schedule, true),
+            _ => Err(anyhow!(
+                "unsupported target schedule type {}",
+                schedule_type
+            )),
+        }
+    }
 }
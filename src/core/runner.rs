@@ -0,0 +1,97 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use anyhow::Result;
+
+/// A signal telling the clients to stop before they finish their operations.
+#[derive(Clone, Default)]
+pub struct Shutdown(Arc<AtomicBool>);
+
+impl Shutdown {
+    pub fn signal(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/// Runs every client on its own OS thread, so that the synchronous `Db`
+/// calls never block the async runtime.
+pub struct ClientRunner {
+    name: String,
+    pin_cpus: bool,
+    shutdown: Shutdown,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl ClientRunner {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            pin_cpus: false,
+            shutdown: Shutdown::default(),
+            handles: Vec::new(),
+        }
+    }
+
+    /// Pins the client threads to the CPU cores in a round-robin way. The
+    /// threads run unpinned if the cores cannot be listed.
+    pub fn pin_cpus(mut self, pin_cpus: bool) -> Self {
+        self.pin_cpus = pin_cpus;
+        self
+    }
+
+    /// Shares the shutdown signal with other runners or tasks.
+    pub fn shutdown_signal(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    pub fn shutdown(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    /// Spawns a client thread named `{name}-{index}`. The client should
+    /// return as soon as the shutdown signal is set.
+    pub fn spawn<F>(&mut self, f: F) -> Result<()>
+    where
+        F: FnOnce(Shutdown) + Send + 'static,
+    {
+        let index = self.handles.len();
+        let core_id = if self.pin_cpus {
+            core_affinity::get_core_ids()
+                .filter(|core_ids| !core_ids.is_empty())
+                .map(|core_ids| core_ids[index % core_ids.len()])
+        } else {
+            None
+        };
+        let shutdown = self.shutdown.clone();
+        let handle = thread::Builder::new()
+            .name(format!("{}-{}", self.name, index))
+            .spawn(move || {
+                if let Some(core_id) = core_id {
+                    core_affinity::set_for_current(core_id);
+                }
+                f(shutdown)
+            })?;
+        self.handles.push(handle);
+        Ok(())
+    }
+
+    /// Waits for all the clients to finish.
+    pub fn join(self) -> Result<()> {
+        let mut panicked = 0;
+        for handle in self.handles {
+            if handle.join().is_err() {
+                panicked += 1;
+            }
+        }
+        if panicked > 0 {
+            return Err(anyhow!("{} client threads panicked", panicked));
+        }
+        Ok(())
+    }
+}
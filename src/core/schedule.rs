@@ -0,0 +1,174 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+/// The target throughput of all clients over the time of a phase, given as
+/// `(time, ops/sec)` points sorted by time. A zero target does not limit the
+/// throughput.
+#[derive(Clone, Debug)]
+pub struct TargetSchedule {
+    points: Vec<(Duration, f64)>,
+    linear: bool,
+}
+
+impl TargetSchedule {
+    pub fn constant(target: f64) -> Self {
+        Self {
+            points: vec![(Duration::default(), target)],
+            linear: false,
+        }
+    }
+
+    /// Parses a schedule like `0s:1000,60s:5000,2m:10000`. The target steps
+    /// at every point, or ramps linearly between the points if `linear`.
+    pub fn parse(schedule: &str, linear: bool) -> Result<Self> {
+        let mut points = Vec::new();
+        for point in schedule.split(',') {
+            let params: Vec<&str> = point.trim().split(':').collect();
+            if params.len() != 2 {
+                return Err(anyhow!("invalid target schedule point {}", point));
+            }
+            let time = parse_duration(params[0].trim())?;
+            let target: f64 = params[1].trim().parse()?;
+            if let Some((last, _)) = points.last() {
+                if time <= *last {
+                    return Err(anyhow!("target schedule times are not increasing"));
+                }
+            }
+            points.push((time, target));
+        }
+        if points.first().map(|(time, _)| *time) != Some(Duration::default()) {
+            return Err(anyhow!("target schedule must start at 0s"));
+        }
+        Ok(Self { points, linear })
+    }
+
+    pub fn points(&self) -> &[(Duration, f64)] {
+        &self.points
+    }
+
+    pub fn is_linear(&self) -> bool {
+        self.linear
+    }
+
+    /// Returns the target ops/sec at `elapsed` since the phase started.
+    pub fn target_at(&self, elapsed: Duration) -> f64 {
+        let next = self.points.iter().position(|(time, _)| *time > elapsed);
+        let (time, target) = match next {
+            Some(i) => self.points[i - 1],
+            None => return self.points.last().unwrap().1,
+        };
+        if !self.linear {
+            return target;
+        }
+        let (next_time, next_target) = self.points[next.unwrap()];
+        let progress = (elapsed - time).as_secs_f64() / (next_time - time).as_secs_f64();
+        target + (next_target - target) * progress
+    }
+}
+
+fn parse_duration(s: &str) -> Result<Duration> {
+    let (num, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let num: u64 = num.parse()?;
+    match unit {
+        "s" => Ok(Duration::from_secs(num)),
+        "m" => Ok(Duration::from_secs(num * 60)),
+        _ => Err(anyhow!("unsupported time unit {}", unit)),
+    }
+}
+
+/// Paces a closed-loop client to its share of a `TargetSchedule`.
+pub struct Throttle {
+    schedule: TargetSchedule,
+    clients: f64,
+    start_time: Instant,
+    next: Instant,
+}
+
+impl Throttle {
+    /// `clients` is the number of clients sharing the schedule, which all
+    /// started at `start_time`.
+    pub fn new(schedule: TargetSchedule, clients: usize, start_time: Instant) -> Self {
+        Self {
+            schedule,
+            clients: clients as f64,
+            start_time,
+            next: start_time,
+        }
+    }
+
+    /// Returns how long the client should wait before its next operation.
+    pub fn delay(&mut self) -> Duration {
+        let now = Instant::now();
+        let target = self.schedule.target_at(now - self.start_time) / self.clients;
+        if target <= 0.0 {
+            self.next = now;
+            return Duration::default();
+        }
+        let start = self.next.max(now);
+        self.next = start + Duration::from_secs_f64(1.0 / target);
+        start - now
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_parse() {
+        let schedule = TargetSchedule::parse("0s:1000, 60:5000 ,2m:10000", false).unwrap();
+        assert_eq!(
+            schedule.points(),
+            &[(secs(0), 1000.0), (secs(60), 5000.0), (secs(120), 10000.0)]
+        );
+        assert!(!schedule.is_linear());
+        assert!(TargetSchedule::parse("0:0", true).unwrap().is_linear());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for schedule in &[
+            "",
+            "0s",
+            "0s:1:2",
+            "0s:fast",
+            "0h:1000",
+            "-1s:1000",
+            "10s:1000",
+            "0s:1000,60s:2000,60s:3000",
+            "0s:1000,2m:2000,60s:3000",
+        ] {
+            assert!(
+                TargetSchedule::parse(schedule, false).is_err(),
+                "{}",
+                schedule
+            );
+        }
+    }
+
+    #[test]
+    fn test_target_at() {
+        let step = TargetSchedule::parse("0s:1000,10s:2000,20s:0", false).unwrap();
+        let linear = TargetSchedule::parse("0s:1000,10s:2000,20s:0", true).unwrap();
+        for &(elapsed, step_target, linear_target) in &[
+            (Duration::default(), 1000.0, 1000.0),
+            (Duration::from_millis(2500), 1000.0, 1250.0),
+            (secs(10), 2000.0, 2000.0),
+            (secs(15), 2000.0, 1000.0),
+            (secs(20), 0.0, 0.0),
+            (secs(100), 0.0, 0.0),
+        ] {
+            assert_eq!(step.target_at(elapsed), step_target, "{:?}", elapsed);
+            assert_eq!(linear.target_at(elapsed), linear_target, "{:?}", elapsed);
+        }
+        assert_eq!(TargetSchedule::constant(500.0).target_at(secs(100)), 500.0);
+    }
+}
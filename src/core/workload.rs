@@ -1,9 +1,24 @@
+mod closed_economy;
+mod consistency;
+mod core_workload;
+mod transactional;
+
+pub use closed_economy::*;
+pub use consistency::*;
+pub use core_workload::*;
+pub use transactional::*;
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+
 use anyhow::Result;
-use rand::{self, distributions, Rng};
+use futures::future::{self, BoxFuture, FutureExt};
 
-use super::db::KvPair;
-use super::generator::*;
-use super::properties::*;
+use super::db::{AsyncDb, AsyncTransaction, Db};
+use super::measurement::Measurements;
+use super::properties::{Properties, WORKLOAD_PROPERTY};
 
 #[derive(Clone, Debug)]
 pub enum Operation {
@@ -14,132 +29,309 @@ pub enum Operation {
     ReadModifyWrite,
 }
 
-pub struct CoreWorkload {
-    table_name: String,
-
-    ordered_inserts: bool,
-    read_all_fields: bool,
-    write_all_fields: bool,
-
-    key_generator: CounterGenerator,
-    op_chooser: DiscreteGenerator<Operation>,
-    request_generator: Box<dyn Generator<u64>>,
-    insert_key_sequence: CounterGenerator,
-
-    scan_len_chooser: Box<dyn Generator<u64>>,
-    field_chooser: UniformGenerator,
-    field_len_generator: Box<dyn Generator<u64>>,
-
-    fields: Vec<String>,
-}
-
-impl CoreWorkload {
-    fn build_key_name(&self, mut num: u64) -> String {
-        if !self.ordered_inserts {
-            num = fxhash::hash64(&num);
+impl Operation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operation::Insert => "INSERT",
+            Operation::Read => "READ",
+            Operation::Update => "UPDATE",
+            Operation::Scan => "SCAN",
+            Operation::ReadModifyWrite => "READ-MODIFY-WRITE",
         }
-        format!("user{}", num)
-    }
-
-    pub fn next_sequence_key(&self) -> String {
-        self.build_key_name(self.key_generator.next())
     }
 
-    pub fn next_transaction_key(&self) -> String {
-        let mut num = self.request_generator.next();
-        while num > self.insert_key_sequence.last() {
-            num = self.request_generator.next();
+    /// Returns `INTENDED-{name}`, see `WorkloadClient::intended_name`.
+    pub fn intended_name(&self) -> &'static str {
+        match self {
+            Operation::Insert => "INTENDED-INSERT",
+            Operation::Read => "INTENDED-READ",
+            Operation::Update => "INTENDED-UPDATE",
+            Operation::Scan => "INTENDED-SCAN",
+            Operation::ReadModifyWrite => "INTENDED-READ-MODIFY-WRITE",
         }
-        self.build_key_name(num)
     }
+}
 
-    fn next_field_name(&self) -> String {
-        format!("field{}", self.field_chooser.next())
-    }
+const OPERATIONS: [Operation; 5] = [
+    Operation::Insert,
+    Operation::Read,
+    Operation::Update,
+    Operation::Scan,
+    Operation::ReadModifyWrite,
+];
 
-    pub fn next_operation(&self) -> Operation {
-        self.op_chooser.next()
+/// Returns `INTENDED-{name}`, which is only allocated if `name` is not the
+/// name of an `Operation`.
+pub fn intended_name(name: &str) -> Cow<'static, str> {
+    match OPERATIONS.iter().find(|op| op.name() == name) {
+        Some(op) => Cow::Borrowed(op.intended_name()),
+        None => Cow::Owned(format!("INTENDED-{}", name)),
     }
+}
 
-    pub fn next_table(&self) -> String {
-        self.table_name.clone()
+/// A workload, created from the properties and shared by all the clients of
+/// the phases running it.
+pub trait Workload: Send + Sync {
+    ///
+    /// Creates the state of the client `index` of the `clients` running a
+    /// phase. The client records its measurements into `measurements`.
+    ///
+    fn init_client(
+        &self,
+        index: usize,
+        clients: usize,
+        measurements: Measurements,
+    ) -> Result<Box<dyn WorkloadClient>>;
+    ///
+    /// Clears any state of the workload after all the clients of a phase
+    /// finished.
+    ///
+    fn cleanup(&self) -> Result<()> {
+        Ok(())
     }
-
-    pub fn next_scan_length(&self) -> u64 {
-        self.scan_len_chooser.next()
+    ///
+    /// Checks the invariant of the workload after the run phase, returning
+    /// its violation, or `None` if it holds or the workload has none.
+    /// Returns an Err if the invariant could not be checked. The calls to the
+    /// `Db` are not measured.
+    ///
+    fn validate(&self, _: &dyn Db) -> Result<Option<String>> {
+        Ok(None)
     }
-
-    pub fn read_fields(&self) -> Vec<String> {
-        if self.read_all_fields {
-            self.fields.clone()
-        } else {
-            vec![self.next_field_name()]
-        }
+    ///
+    /// Checks the invariant of the workload after the run phase, see
+    /// `validate`.
+    ///
+    fn validate_async<'a>(&'a self, _: &'a dyn AsyncDb) -> BoxFuture<'a, Result<Option<String>>> {
+        future::ready(Ok(None)).boxed()
     }
+}
 
-    pub fn write_all_fields(&self) -> bool {
-        self.write_all_fields
+/// The state of a client of a `Workload`, running its operations against the
+/// `Db` or `AsyncDb` of the client. Every call to the `Db` is measured by the
+/// client.
+pub trait WorkloadClient: Send + Sync {
+    ///
+    /// Inserts the next `count` records of the load phase.
+    ///
+    fn do_insert(&self, db: &dyn Db, count: u64) -> Result<()>;
+    ///
+    /// Runs the next operation of the run phase, returning its name and
+    /// result.
+    ///
+    fn do_transaction(&self, db: &dyn Db) -> (&'static str, Result<()>);
+    ///
+    /// Inserts the next `count` records of the load phase, see `do_insert`.
+    ///
+    fn do_insert_async<'a>(&'a self, db: &'a dyn AsyncDb, count: u64) -> BoxFuture<'a, Result<()>>;
+    ///
+    /// Runs the next operation of the run phase, see `do_transaction`.
+    ///
+    fn do_transaction_async<'a>(
+        &'a self,
+        db: &'a dyn AsyncDb,
+    ) -> BoxFuture<'a, (&'static str, Result<()>)>;
+    ///
+    /// Returns the name under which the latency of the operation `name`,
+    /// returned by `do_transaction`, is measured from its intended start in
+    /// open loop. A client whose operations are not `Operation`s returns
+    /// their `INTENDED-{name}` names here, so that they are not allocated.
+    ///
+    fn intended_name(&self, name: &'static str) -> Cow<'static, str> {
+        intended_name(name)
     }
+}
 
-    pub fn new(props: &Properties) -> Result<Self> {
-        let table_name = props.get_property(TABLENAME_PROPERTY);
-        let field_count: u64 = props.get_property(FIELD_COUNT_PROPERTY).parse()?;
+type WorkloadFactory = Arc<dyn Fn(&Properties) -> Result<Arc<dyn Workload>> + Send + Sync>;
 
-        let insert_start: u64 = props.get_property(INSERT_START_PROPERTY).parse()?;
-        let key_generator = CounterGenerator::new(insert_start);
+lazy_static! {
+    static ref WORKLOAD_FACTORIES: RwLock<BTreeMap<String, WorkloadFactory>> = {
+        let mut factories: BTreeMap<String, WorkloadFactory> = BTreeMap::new();
+        factories.insert(
+            "core".to_string(),
+            Arc::new(|props: &Properties| {
+                Ok(Arc::new(CoreWorkload::new(props)?) as Arc<dyn Workload>)
+            }),
+        );
+        factories.insert(
+            "transactional".to_string(),
+            Arc::new(|props: &Properties| {
+                Ok(Arc::new(TransactionalWorkload::new(props)?) as Arc<dyn Workload>)
+            }),
+        );
+        factories.insert(
+            "closedeconomy".to_string(),
+            Arc::new(|props: &Properties| {
+                Ok(Arc::new(ClosedEconomyWorkload::new(props)?) as Arc<dyn Workload>)
+            }),
+        );
+        factories.insert(
+            "consistency".to_string(),
+            Arc::new(|props: &Properties| {
+                Ok(Arc::new(ConsistencyWorkload::new(props)?) as Arc<dyn Workload>)
+            }),
+        );
+        RwLock::new(factories)
+    };
+}
 
-        let record_count = props.get_record_count();
-        let insert_key_sequence = CounterGenerator::new(record_count);
+/// Registers the workload `name`, replacing any workload registered with the
+/// same name.
+pub fn register_workload<F>(name: &str, factory: F)
+where
+    F: Fn(&Properties) -> Result<Arc<dyn Workload>> + Send + Sync + 'static,
+{
+    WORKLOAD_FACTORIES
+        .write()
+        .unwrap()
+        .insert(name.to_string(), Arc::new(factory));
+}
 
-        let op_chooser = props.get_operation_generator()?;
-        let request_generator = props.get_request_generator()?;
-        let field_len_generator = props.get_field_len_generator()?;
-        let scan_len_chooser = props.get_scan_length_generator()?;
-        let field_chooser = UniformGenerator::new().max(field_count - 1);
+/// Returns the sorted names of all the registered workloads.
+pub fn workload_names() -> Vec<String> {
+    WORKLOAD_FACTORIES.read().unwrap().keys().cloned().collect()
+}
 
-        let read_all_fields: bool = props.get_property(READ_ALL_FIELDS_PROPERTY).parse()?;
-        let write_all_fields: bool = props.get_property(WRITE_ALL_FIELDS_PROPERTY).parse()?;
-        let ordered_inserts = props.get_property(INSERT_ORDER_PROPERTY) == "hashed";
+/// Creates the workload selected by the `workload` property.
+pub fn create_workload(props: &Properties) -> Result<Arc<dyn Workload>> {
+    let name = props.get_property(WORKLOAD_PROPERTY);
+    let factory = WORKLOAD_FACTORIES.read().unwrap().get(&name).cloned();
+    match factory {
+        Some(factory) => factory(props),
+        None => Err(anyhow!("unsupported workload {}", name)),
+    }
+}
 
-        Ok(Self {
-            table_name,
+/// Splits `total` operations among `clients`, handing the remainder to the
+/// first clients. Returns the number of operations of client `index`; the
+/// keys of the operations come from the key sequences shared by the clients.
+pub fn split_ops(total: u64, clients: usize, index: usize) -> u64 {
+    let (clients, index) = (clients as u64, index as u64);
+    let (base, rem) = (total / clients, total % clients);
+    if index < rem {
+        base + 1
+    } else {
+        base
+    }
+}
 
-            ordered_inserts,
-            read_all_fields,
-            write_all_fields,
+/// Runs `f` in a transaction of `db`, which is aborted if `f` fails. `f`
+/// makes its calls on the `Db` it is given, which is `db` itself if `db` has
+/// no transactions. The outcome, including a failed start, is counted as
+/// `COMMITTED` or `ABORTED`.
+fn in_transaction<F>(db: &dyn Db, measurements: &Measurements, f: F) -> Result<()>
+where
+    F: FnOnce(&dyn Db) -> Result<()>,
+{
+    let transaction = match db.start() {
+        Ok(transaction) => transaction,
+        Err(e) => {
+            measurements.count("ABORTED");
+            return Err(e);
+        }
+    };
+    let result = match &transaction {
+        Some(transaction) => f(transaction.as_db()),
+        None => f(db),
+    };
+    if let Err(e) = result {
+        measurements.count("ABORTED");
+        if let Some(transaction) = transaction {
+            let _ = transaction.abort();
+        }
+        return Err(e);
+    }
+    let result = match transaction {
+        Some(transaction) => transaction.commit(),
+        None => Ok(()),
+    };
+    match result {
+        Ok(_) => measurements.count("COMMITTED"),
+        Err(_) => measurements.count("ABORTED"),
+    }
+    result
+}
 
-            key_generator,
-            op_chooser,
-            request_generator,
-            insert_key_sequence,
+/// The `AsyncDb` a transaction makes its calls on, see
+/// `in_transaction_async`.
+enum TransactionDb<'a> {
+    Db(&'a dyn AsyncDb),
+    Transaction(Arc<dyn AsyncTransaction>),
+}
 
-            scan_len_chooser,
-            field_chooser,
-            field_len_generator,
+impl TransactionDb<'_> {
+    fn as_db(&self) -> &dyn AsyncDb {
+        match self {
+            TransactionDb::Db(db) => *db,
+            TransactionDb::Transaction(transaction) => transaction.as_db(),
+        }
+    }
+}
 
-            fields: (0..field_count).map(|i| format!("field{}", i)).collect(),
-        })
+/// Runs the future built by `f` in a transaction of `db`, see
+/// `in_transaction`.
+async fn in_transaction_async<'a, F, T>(
+    db: &'a dyn AsyncDb,
+    measurements: &Measurements,
+    f: F,
+) -> Result<()>
+where
+    F: FnOnce(TransactionDb<'a>) -> T,
+    T: Future<Output = Result<()>>,
+{
+    let transaction = match db.start().await {
+        Ok(transaction) => transaction,
+        Err(e) => {
+            measurements.count("ABORTED");
+            return Err(e);
+        }
+    };
+    let result = match &transaction {
+        Some(transaction) => f(TransactionDb::Transaction(transaction.clone())).await,
+        None => f(TransactionDb::Db(db)).await,
+    };
+    if let Err(e) = result {
+        measurements.count("ABORTED");
+        if let Some(transaction) = transaction {
+            let _ = transaction.abort().await;
+        }
+        return Err(e);
     }
+    let result = match transaction {
+        Some(transaction) => transaction.commit().await,
+        None => Ok(()),
+    };
+    match result {
+        Ok(_) => measurements.count("COMMITTED"),
+        Err(_) => measurements.count("ABORTED"),
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    pub fn build_values(&self) -> Vec<KvPair> {
-        self.fields
-            .iter()
-            .map(|field| {
-                let value: String =
-                    std::iter::repeat(rand::thread_rng().sample(distributions::Alphanumeric))
-                        .take(self.field_len_generator.next() as usize)
-                        .collect();
-                (field.clone(), value)
-            })
-            .collect()
+    #[test]
+    fn test_intended_name() {
+        for op in &OPERATIONS {
+            let name = intended_name(op.name());
+            assert!(matches!(name, Cow::Borrowed(_)));
+            assert_eq!(name, format!("INTENDED-{}", op.name()));
+        }
+        assert_eq!(intended_name("OTHER"), "INTENDED-OTHER");
     }
 
-    pub fn build_update(&self) -> Vec<KvPair> {
-        let field = self.next_field_name();
-        let value: String =
-            std::iter::repeat(rand::thread_rng().sample(distributions::Alphanumeric))
-                .take(self.field_len_generator.next() as usize)
-                .collect();
-        vec![(field, value)]
+    #[test]
+    fn test_split_ops() {
+        for &(total, clients) in &[(100_000, 3), (10, 4), (2, 5), (0, 2), (7, 1)] {
+            let counts = (0..clients)
+                .map(|i| split_ops(total, clients, i))
+                .collect::<Vec<_>>();
+            assert_eq!(counts.iter().sum::<u64>(), total);
+            let (min, max) = (counts.iter().min(), counts.iter().max());
+            assert!(max.unwrap() - min.unwrap() <= 1, "{:?}", counts);
+            assert!(counts.windows(2).all(|w| w[0] >= w[1]), "{:?}", counts);
+        }
     }
 }
@@ -0,0 +1,295 @@
+use std::borrow::Cow;
+use std::io::Write;
+use std::time::Instant;
+
+use anyhow::Result;
+use futures::future::{BoxFuture, FutureExt};
+use rand::{self, Rng};
+
+use super::{in_transaction, in_transaction_async, Buffers, CoreClient, CoreWorkload};
+use super::{Workload, WorkloadClient};
+use crate::core::db::{AsyncDb, Db, KvPair, Record};
+use crate::core::measurement::Measurements;
+use crate::core::properties::Properties;
+
+const BALANCE_FIELD: &str = "balance";
+
+/// A bank of `recordcount` accounts sharing `totalcash`, rounded down to a
+/// multiple of the number of accounts. Every operation transfers money
+/// between two accounts in a transaction, measured as `TRANSFER`, so the
+/// total balance must never change under a correct isolation level.
+pub struct ClosedEconomyWorkload {
+    core: CoreWorkload,
+    record_count: u64,
+    initial_balance: u64,
+}
+
+impl ClosedEconomyWorkload {
+    pub fn new(props: &Properties) -> Result<Self> {
+        let record_count = props.get_record_count();
+        if record_count < 2 {
+            return Err(anyhow!("closed economy requires at least 2 accounts"));
+        }
+        Ok(Self {
+            core: CoreWorkload::new(props)?,
+            record_count,
+            initial_balance: props.get_total_cash() / record_count,
+        })
+    }
+
+    pub fn total_cash(&self) -> u64 {
+        self.initial_balance * self.record_count
+    }
+
+    /// Returns how the scanned accounts violate the total balance, or `None`
+    /// if they still hold it.
+    fn check_accounts(&self, accounts: &[Record]) -> Option<String> {
+        if accounts.len() as u64 != self.record_count {
+            return Some(format!(
+                "found {} accounts, expected {}",
+                accounts.len(),
+                self.record_count
+            ));
+        }
+        let mut total: u64 = 0;
+        for (key, account) in accounts {
+            let balance = match balance(account) {
+                Ok(balance) => balance,
+                Err(e) => {
+                    let key = String::from_utf8_lossy(key);
+                    return Some(format!("account {}: {}", key, e));
+                }
+            };
+            total = match total.checked_add(balance) {
+                Some(total) => total,
+                None => {
+                    let key = String::from_utf8_lossy(key);
+                    return Some(format!("total balance overflows at account {}", key));
+                }
+            };
+        }
+        if total != self.total_cash() {
+            return Some(format!(
+                "total balance is {}, expected {}",
+                total,
+                self.total_cash()
+            ));
+        }
+        None
+    }
+}
+
+impl Workload for ClosedEconomyWorkload {
+    fn init_client(
+        &self,
+        _: usize,
+        _: usize,
+        measurements: Measurements,
+    ) -> Result<Box<dyn WorkloadClient>> {
+        Ok(Box::new(ClosedEconomyClient {
+            core: self.core.init_core_client(measurements)?,
+            initial_balance: self.initial_balance,
+            balance_fields: vec![BALANCE_FIELD.to_string()],
+        }))
+    }
+
+    /// Scans all the accounts to check their total balance.
+    fn validate(&self, db: &dyn Db) -> Result<Option<String>> {
+        let mut first = Vec::new();
+        self.core.build_first_key(self.record_count, &mut first);
+        let accounts = db.scan(
+            self.core.table_name(),
+            &first,
+            &[BALANCE_FIELD.to_string()],
+            self.record_count,
+        )?;
+        Ok(self.check_accounts(&accounts))
+    }
+
+    fn validate_async<'a>(&'a self, db: &'a dyn AsyncDb) -> BoxFuture<'a, Result<Option<String>>> {
+        async move {
+            let mut first = Vec::new();
+            self.core.build_first_key(self.record_count, &mut first);
+            let accounts = db
+                .scan(
+                    self.core.table_name(),
+                    &first,
+                    &[BALANCE_FIELD.to_string()],
+                    self.record_count,
+                )
+                .await?;
+            Ok(self.check_accounts(&accounts))
+        }
+        .boxed()
+    }
+}
+
+/// The state of a client of the `ClosedEconomyWorkload`.
+pub struct ClosedEconomyClient {
+    core: CoreClient,
+    initial_balance: u64,
+    balance_fields: Vec<String>,
+}
+
+impl ClosedEconomyClient {
+    /// Builds an account of the load phase with its initial balance.
+    fn build_account(&self, values: &mut Vec<KvPair>) {
+        build_balance(self.initial_balance, values)
+    }
+
+    /// Builds the keys of two different accounts to transfer between.
+    fn next_accounts(&self, from: &mut Vec<u8>, to: &mut Vec<u8>) {
+        self.core.next_transaction_key(from);
+        loop {
+            self.core.next_transaction_key(to);
+            if to != from {
+                return;
+            }
+        }
+    }
+
+    /// Returns the amount to transfer from an account holding `balance`.
+    fn next_amount(&self, balance: u64) -> u64 {
+        rand::thread_rng().gen_range(0, balance + 1)
+    }
+
+    /// Transfers money between two accounts, building their keys and
+    /// balances into `from` and `to`.
+    fn do_transfer(&self, db: &dyn Db, from: &mut Buffers, to: &mut Buffers) -> Result<()> {
+        let table = self.core.next_table();
+        self.next_accounts(&mut from.key, &mut to.key);
+        let from_balance = balance(&db.read(table, &from.key, &self.balance_fields)?)?;
+        let to_balance = balance(&db.read(table, &to.key, &self.balance_fields)?)?;
+        let amount = self.next_amount(from_balance);
+        build_balance(from_balance - amount, &mut from.values);
+        build_balance(to_balance + amount, &mut to.values);
+        db.update(table, &from.key, &from.values)?;
+        db.update(table, &to.key, &to.values)
+    }
+
+    async fn do_transfer_async(
+        &self,
+        db: &dyn AsyncDb,
+        from: &mut Buffers,
+        to: &mut Buffers,
+    ) -> Result<()> {
+        let table = self.core.next_table();
+        self.next_accounts(&mut from.key, &mut to.key);
+        let from_values = db.read(table, &from.key, &self.balance_fields).await?;
+        let to_values = db.read(table, &to.key, &self.balance_fields).await?;
+        let (from_balance, to_balance) = (balance(&from_values)?, balance(&to_values)?);
+        let amount = self.next_amount(from_balance);
+        build_balance(from_balance - amount, &mut from.values);
+        build_balance(to_balance + amount, &mut to.values);
+        db.update(table, &from.key, &from.values).await?;
+        db.update(table, &to.key, &to.values).await
+    }
+}
+
+impl WorkloadClient for ClosedEconomyClient {
+    fn do_insert(&self, db: &dyn Db, count: u64) -> Result<()> {
+        self.core
+            .insert_records(db, count, |_, values| self.build_account(values))
+    }
+
+    fn do_transaction(&self, db: &dyn Db) -> (&'static str, Result<()>) {
+        let measurements = self.core.measurements();
+        let (mut from, mut to) = (self.core.take_buffers(), self.core.take_buffers());
+        let start = Instant::now();
+        let result = in_transaction(db, measurements, |db| {
+            self.do_transfer(db, &mut from, &mut to)
+        });
+        measurements.measure("TRANSFER", start.elapsed(), result.is_ok());
+        self.core.put_buffers(from);
+        self.core.put_buffers(to);
+        ("TRANSFER", result)
+    }
+
+    fn do_insert_async<'a>(&'a self, db: &'a dyn AsyncDb, count: u64) -> BoxFuture<'a, Result<()>> {
+        self.core
+            .insert_records_async(db, count, move |_, values| self.build_account(values))
+            .boxed()
+    }
+
+    fn do_transaction_async<'a>(
+        &'a self,
+        db: &'a dyn AsyncDb,
+    ) -> BoxFuture<'a, (&'static str, Result<()>)> {
+        async move {
+            let measurements = self.core.measurements();
+            let (mut from, mut to) = (self.core.take_buffers(), self.core.take_buffers());
+            let start = Instant::now();
+            let (from_buffers, to_buffers) = (&mut from, &mut to);
+            let result = in_transaction_async(db, measurements, |db| async move {
+                self.do_transfer_async(db.as_db(), from_buffers, to_buffers)
+                    .await
+            })
+            .await;
+            measurements.measure("TRANSFER", start.elapsed(), result.is_ok());
+            self.core.put_buffers(from);
+            self.core.put_buffers(to);
+            ("TRANSFER", result)
+        }
+        .boxed()
+    }
+
+    fn intended_name(&self, _: &'static str) -> Cow<'static, str> {
+        Cow::Borrowed("INTENDED-TRANSFER")
+    }
+}
+
+/// Builds the account holding `balance` into `values`.
+fn build_balance(balance: u64, values: &mut Vec<KvPair>) {
+    values.resize_with(1, Default::default);
+    let (field, value) = &mut values[0];
+    if field != BALANCE_FIELD {
+        field.clear();
+        field.push_str(BALANCE_FIELD);
+    }
+    value.clear();
+    let _ = write!(value, "{}", balance);
+}
+
+/// Parses the balance of an account read from the `Db`.
+fn balance(values: &[KvPair]) -> Result<u64> {
+    let (_, balance) = values
+        .iter()
+        .find(|(field, _)| field == BALANCE_FIELD)
+        .ok_or_else(|| anyhow!("account has no {} field", BALANCE_FIELD))?;
+    Ok(std::str::from_utf8(balance)?.parse()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() -> Result<()> {
+        let props = Properties::load("recordcount=10\ntotalcash=1005".as_bytes())?;
+        let workload = ClosedEconomyWorkload::new(&props)?;
+        assert_eq!(workload.total_cash(), 1000);
+        let db = crate::db::create_db("btree", &Properties::default())?;
+        let client = workload.init_client(0, 1, Measurements::new())?;
+        client.do_insert(&*db, 10)?;
+        for _ in 0..100 {
+            client.do_transaction(&*db).1?;
+        }
+        assert_eq!(workload.validate(&*db)?, None);
+
+        // Money appears in an account.
+        let mut first = Vec::new();
+        workload.core.build_first_key(10, &mut first);
+        let table = workload.core.table_name();
+        let mut values = db.read(table, &first, &[])?;
+        let balance = balance(&values)?;
+        build_balance(balance + 1, &mut values);
+        db.update(table, &first, &values)?;
+        let violation = workload.validate(&*db)?.unwrap();
+        assert_eq!(violation, "total balance is 1001, expected 1000");
+
+        db.delete(table, &first)?;
+        let violation = workload.validate(&*db)?.unwrap();
+        assert_eq!(violation, "found 9 accounts, expected 10");
+        Ok(())
+    }
+}
@@ -0,0 +1,565 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::Result;
+use dashmap::DashMap;
+use futures::future::{BoxFuture, FutureExt};
+
+use super::{Buffers, CoreClient, CoreWorkload, Operation};
+use super::{Workload, WorkloadClient};
+use crate::core::db::{AsyncDb, Db, KvPair};
+use crate::core::measurement::Measurements;
+use crate::core::properties::Properties;
+
+const VERSION_FIELD: &str = "version";
+
+/// The number of the latest writes of a key kept to check the reads of the
+/// key, which bounds the staleness of a read in versions.
+const MAX_WRITES: usize = 64;
+
+/// A core workload checking the consistency of the reads of a replicated
+/// `Db`. Every update writes a new version into the `version` field of its
+/// record, the versions growing over all the keys, and every read checks the
+/// version it returns against the updates acknowledged before it started.
+///
+/// A read is stale if it misses an update that was acknowledged before the
+/// read started and issued after the update of the version it returned was
+/// acknowledged, so that concurrent updates of a key are never counted. A
+/// stale read is counted as `STALE-READ`, and also as
+/// `READ-YOUR-WRITES-VIOLATION` if its client issued one of the missed
+/// updates. The staleness is measured as `STALENESS`, the time since the
+/// first missed update was acknowledged, and counted by the number of missed
+/// updates as `STALENESS-VERSIONS-<range>`.
+///
+/// Read-modify-writes check their reads too, while inserts and scans run as
+/// in the core workload. The records of the load phase and the inserted
+/// records have the version 0.
+pub struct ConsistencyWorkload {
+    core: CoreWorkload,
+    writes: Arc<WriteLog>,
+}
+
+impl ConsistencyWorkload {
+    pub fn new(props: &Properties) -> Result<Self> {
+        Ok(Self {
+            core: CoreWorkload::new(props)?,
+            writes: Arc::new(WriteLog::default()),
+        })
+    }
+}
+
+impl Workload for ConsistencyWorkload {
+    fn init_client(
+        &self,
+        index: usize,
+        _: usize,
+        measurements: Measurements,
+    ) -> Result<Box<dyn WorkloadClient>> {
+        Ok(Box::new(ConsistencyClient {
+            core: self.core.init_core_client(measurements)?,
+            index,
+            writes: self.writes.clone(),
+            version_fields: vec![VERSION_FIELD.to_string()],
+        }))
+    }
+}
+
+/// An update of a key, by the client `client`.
+struct VersionWrite {
+    version: u64,
+    client: usize,
+    issued: Instant,
+    acknowledged: Option<Instant>,
+}
+
+/// The latest updates of a key, and the starts of its reads in flight.
+#[derive(Default)]
+struct KeyLog {
+    writes: VecDeque<VersionWrite>,
+    reads: Vec<Instant>,
+}
+
+/// The updates of every updated key, shared by all the clients of a phase.
+///
+/// Once an update `L` of a key is acknowledged before all the reads of the
+/// key in flight started, the updates acknowledged before `L` was issued
+/// are dropped: a read returning one of them misses `L`, so it is still
+/// found stale, but its staleness only counts the updates left in the log.
+struct WriteLog {
+    next_version: AtomicU64,
+    keys: DashMap<Vec<u8>, KeyLog>,
+}
+
+impl Default for WriteLog {
+    fn default() -> Self {
+        Self {
+            next_version: AtomicU64::new(1),
+            keys: DashMap::new(),
+        }
+    }
+}
+
+/// A read of a key, see `WriteLog::start_read`.
+struct VersionRead {
+    start: Instant,
+    logged: bool,
+}
+
+/// The updates a stale read missed.
+#[derive(Debug)]
+struct Staleness {
+    versions: u64,
+    since: Instant,
+    own_write: bool,
+}
+
+impl WriteLog {
+    /// Logs an update of `key` by `client` issued at `now`, returning its
+    /// version. The versions of a key are logged in increasing order.
+    fn issue(&self, key: &[u8], client: usize, now: Instant) -> u64 {
+        let mut log = match self.keys.get_mut(key) {
+            Some(log) => log,
+            None => self.keys.entry(key.to_vec()).or_default(),
+        };
+        let version = self.next_version.fetch_add(1, Ordering::Relaxed);
+        if log.writes.len() == MAX_WRITES {
+            log.writes.pop_front();
+        }
+        log.writes.push_back(VersionWrite {
+            version,
+            client,
+            issued: now,
+            acknowledged: None,
+        });
+        version
+    }
+
+    /// Logs the acknowledgement of the update `version` of `key` at `now`,
+    /// and drops the updates no read needs anymore. A failed update is never
+    /// acknowledged, since it may have been applied.
+    fn acknowledge(&self, key: &[u8], version: u64, now: Instant) {
+        let mut log = match self.keys.get_mut(key) {
+            Some(log) => log,
+            None => return,
+        };
+        if let Some(write) = log.writes.iter_mut().find(|w| w.version == version) {
+            write.acknowledged = Some(now);
+        }
+        let oldest_read = log.reads.iter().min().copied().unwrap_or(now);
+        let latest = log
+            .writes
+            .iter()
+            .rev()
+            .find(|w| matches!(w.acknowledged, Some(a) if a < oldest_read))
+            .map(|w| (w.version, w.issued));
+        if let Some((latest, issued)) = latest {
+            log.writes.retain(|w| {
+                w.version >= latest || !matches!(w.acknowledged, Some(a) if a < issued)
+            });
+        }
+    }
+
+    /// Logs a read of `key` started at `now`, which must be finished by
+    /// `finish_read`. A read of a key without updates is not logged, since
+    /// the updates issued after it starts are never missed.
+    fn start_read(&self, key: &[u8], now: Instant) -> VersionRead {
+        let logged = match self.keys.get_mut(key) {
+            Some(mut log) => {
+                log.reads.push(now);
+                true
+            }
+            None => false,
+        };
+        VersionRead { start: now, logged }
+    }
+
+    /// Finishes `read` of `key` by `client`, which returned `version` unless
+    /// it failed. Returns the updates the read missed, or `None` if it is not
+    /// stale.
+    fn finish_read(
+        &self,
+        key: &[u8],
+        client: usize,
+        read: VersionRead,
+        version: Option<u64>,
+    ) -> Option<Staleness> {
+        let mut log = self.keys.get_mut(key)?;
+        if read.logged {
+            if let Some(index) = log.reads.iter().position(|r| *r == read.start) {
+                log.reads.swap_remove(index);
+            }
+        }
+        check(&log.writes, client, version?, read.start)
+    }
+}
+
+/// Returns the `writes` of a key missed by a read by `client` started at
+/// `start`, which returned `version`, or `None` if the read is not stale.
+fn check(
+    writes: &VecDeque<VersionWrite>,
+    client: usize,
+    version: u64,
+    start: Instant,
+) -> Option<Staleness> {
+    // `None` for the load phase, or an update older than the logged ones.
+    let mut returned = None;
+    if let Some(write) = writes.iter().find(|w| w.version == version) {
+        // The update may still be applied after the read.
+        returned = Some(write.acknowledged?);
+    }
+    let mut staleness: Option<Staleness> = None;
+    for write in writes.iter().filter(|w| w.version > version) {
+        let acknowledged = match write.acknowledged {
+            Some(acknowledged) if acknowledged < start => acknowledged,
+            _ => continue,
+        };
+        match returned {
+            Some(returned) if write.issued <= returned => continue,
+            _ => {}
+        }
+        let staleness = staleness.get_or_insert(Staleness {
+            versions: 0,
+            since: acknowledged,
+            own_write: false,
+        });
+        staleness.versions += 1;
+        staleness.since = staleness.since.min(acknowledged);
+        staleness.own_write |= write.client == client;
+    }
+    staleness
+}
+
+/// The state of a client of the `ConsistencyWorkload`.
+pub struct ConsistencyClient {
+    core: CoreClient,
+    index: usize,
+    writes: Arc<WriteLog>,
+    version_fields: Vec<String>,
+}
+
+impl ConsistencyClient {
+    /// Builds a record of the load phase, with the version 0.
+    fn build_record(&self, key: &[u8], values: &mut Vec<KvPair>) {
+        self.core.build_values(key, values);
+        push_version(0, values);
+    }
+
+    /// Builds an update of `key` into `values`, returning its version, which
+    /// must be acknowledged by `acknowledge` once written.
+    fn build_versioned_update(&self, key: &[u8], values: &mut Vec<KvPair>) -> u64 {
+        if self.core.write_all_fields() {
+            self.core.build_values(key, values);
+        } else {
+            self.core.build_update(key, values);
+        }
+        let version = self.writes.issue(key, self.index, Instant::now());
+        push_version(version, values);
+        version
+    }
+
+    fn acknowledge(&self, key: &[u8], version: u64, result: &Result<()>) {
+        if result.is_ok() {
+            self.writes.acknowledge(key, version, Instant::now());
+        }
+    }
+
+    /// Checks the version returned by `read` of `key`, unless it failed.
+    fn check_read(&self, key: &[u8], read: VersionRead, row: Result<Vec<KvPair>>) -> Result<()> {
+        let start = read.start;
+        let version = row.and_then(|values| version(&values));
+        let returned = version.as_ref().ok().copied();
+        let staleness = self.writes.finish_read(key, self.index, read, returned);
+        version?;
+        let staleness = match staleness {
+            Some(staleness) => staleness,
+            None => return Ok(()),
+        };
+        let measurements = self.core.measurements();
+        measurements.count("STALE-READ");
+        if staleness.own_write {
+            measurements.count("READ-YOUR-WRITES-VIOLATION");
+        }
+        measurements.count(staleness_versions_name(staleness.versions));
+        measurements.measure("STALENESS", start - staleness.since, true);
+        Ok(())
+    }
+
+    fn do_operation(&self, db: &dyn Db, op: &Operation, buffers: &mut Buffers) -> Result<()> {
+        let table = self.core.next_table();
+        let Buffers { key, values, .. } = buffers;
+        self.core.next_transaction_key(key);
+        if let Operation::Read | Operation::ReadModifyWrite = op {
+            let read = self.writes.start_read(key, Instant::now());
+            let row = db.read(table, key, &self.version_fields);
+            self.check_read(key, read, row)?;
+        }
+        if let Operation::Update | Operation::ReadModifyWrite = op {
+            let version = self.build_versioned_update(key, values);
+            let result = db.update(table, key, values);
+            self.acknowledge(key, version, &result);
+            result?;
+        }
+        Ok(())
+    }
+
+    async fn do_operation_async(
+        &self,
+        db: &dyn AsyncDb,
+        op: &Operation,
+        buffers: &mut Buffers,
+    ) -> Result<()> {
+        let table = self.core.next_table();
+        let Buffers { key, values, .. } = buffers;
+        self.core.next_transaction_key(key);
+        if let Operation::Read | Operation::ReadModifyWrite = op {
+            let read = self.writes.start_read(key, Instant::now());
+            let row = db.read(table, key, &self.version_fields).await;
+            self.check_read(key, read, row)?;
+        }
+        if let Operation::Update | Operation::ReadModifyWrite = op {
+            let version = self.build_versioned_update(key, values);
+            let result = db.update(table, key, values).await;
+            self.acknowledge(key, version, &result);
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Runs `op`, measuring a read-modify-write as a whole.
+    fn do_measured_operation(&self, db: &dyn Db, op: Operation) -> Result<()> {
+        let mut buffers = self.core.take_buffers();
+        let start = Instant::now();
+        let result = self.do_operation(db, &op, &mut buffers);
+        if let Operation::ReadModifyWrite = op {
+            self.core
+                .measurements()
+                .measure(op.name(), start.elapsed(), result.is_ok());
+        }
+        self.core.put_buffers(buffers);
+        result
+    }
+
+    /// Runs `op`, see `do_measured_operation`.
+    async fn do_measured_operation_async(&self, db: &dyn AsyncDb, op: Operation) -> Result<()> {
+        let mut buffers = self.core.take_buffers();
+        let start = Instant::now();
+        let result = self.do_operation_async(db, &op, &mut buffers).await;
+        if let Operation::ReadModifyWrite = op {
+            self.core
+                .measurements()
+                .measure(op.name(), start.elapsed(), result.is_ok());
+        }
+        self.core.put_buffers(buffers);
+        result
+    }
+}
+
+impl WorkloadClient for ConsistencyClient {
+    fn do_insert(&self, db: &dyn Db, count: u64) -> Result<()> {
+        self.core
+            .insert_records(db, count, |key, values| self.build_record(key, values))
+    }
+
+    fn do_transaction(&self, db: &dyn Db) -> (&'static str, Result<()>) {
+        let op = self.core.next_operation();
+        let result = match op {
+            Operation::Read | Operation::Update | Operation::ReadModifyWrite => {
+                self.do_measured_operation(db, op.clone())
+            }
+            _ => self.core.do_measured_operation(db, op.clone()),
+        };
+        (op.name(), result)
+    }
+
+    fn do_insert_async<'a>(&'a self, db: &'a dyn AsyncDb, count: u64) -> BoxFuture<'a, Result<()>> {
+        self.core
+            .insert_records_async(db, count, move |key, values| self.build_record(key, values))
+            .boxed()
+    }
+
+    fn do_transaction_async<'a>(
+        &'a self,
+        db: &'a dyn AsyncDb,
+    ) -> BoxFuture<'a, (&'static str, Result<()>)> {
+        async move {
+            let op = self.core.next_operation();
+            let result = match op {
+                Operation::Read | Operation::Update | Operation::ReadModifyWrite => {
+                    self.do_measured_operation_async(db, op.clone()).await
+                }
+                _ => self.core.do_measured_operation_async(db, op.clone()).await,
+            };
+            (op.name(), result)
+        }
+        .boxed()
+    }
+}
+
+/// Appends the `version` field to `values`.
+fn push_version(version: u64, values: &mut Vec<KvPair>) {
+    let mut value = Vec::new();
+    let _ = write!(value, "{}", version);
+    values.push((VERSION_FIELD.to_string(), value));
+}
+
+/// Parses the version of a record read from the `Db`, which is 0 for a
+/// record without a version.
+fn version(values: &[KvPair]) -> Result<u64> {
+    match values.iter().find(|(field, _)| field == VERSION_FIELD) {
+        Some((_, version)) => Ok(std::str::from_utf8(version)?.parse()?),
+        None => Ok(0),
+    }
+}
+
+/// Returns the name of the counter of the reads that missed `versions`
+/// updates, by powers of two.
+fn staleness_versions_name(versions: u64) -> &'static str {
+    match versions {
+        0..=1 => "STALENESS-VERSIONS-01",
+        2..=3 => "STALENESS-VERSIONS-02-03",
+        4..=7 => "STALENESS-VERSIONS-04-07",
+        8..=15 => "STALENESS-VERSIONS-08-15",
+        16..=31 => "STALENESS-VERSIONS-16-31",
+        _ => "STALENESS-VERSIONS-32+",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    const KEY: &[u8] = b"user1";
+
+    struct Clock(Instant);
+
+    impl Clock {
+        fn at(&self, millis: u64) -> Instant {
+            self.0 + Duration::from_millis(millis)
+        }
+    }
+
+    fn read(log: &WriteLog, client: usize, start: Instant, version: u64) -> Option<Staleness> {
+        let read = log.start_read(KEY, start);
+        log.finish_read(KEY, client, read, Some(version))
+    }
+
+    fn logged(log: &WriteLog) -> Vec<u64> {
+        let key = log.keys.get(KEY).unwrap();
+        key.writes.iter().map(|w| w.version).collect()
+    }
+
+    #[test]
+    fn test_stale_read() {
+        let clock = Clock(Instant::now());
+        let log = WriteLog::default();
+        let version = log.issue(KEY, 0, clock.at(0));
+        log.acknowledge(KEY, version, clock.at(1));
+        assert!(read(&log, 1, clock.at(1), 0).is_none());
+        let staleness = read(&log, 1, clock.at(2), 0).unwrap();
+        assert_eq!(staleness.versions, 1);
+        assert_eq!(staleness.since, clock.at(1));
+        assert!(!staleness.own_write);
+        assert!(read(&log, 1, clock.at(2), version).is_none());
+    }
+
+    #[test]
+    fn test_concurrent_updates() {
+        let clock = Clock(Instant::now());
+        let log = WriteLog::default();
+        let first = log.issue(KEY, 0, clock.at(0));
+        let second = log.issue(KEY, 1, clock.at(1));
+        log.acknowledge(KEY, second, clock.at(2));
+        log.acknowledge(KEY, first, clock.at(3));
+        // The second update may have been applied before the first.
+        assert!(read(&log, 2, clock.at(4), first).is_none());
+        let staleness = read(&log, 2, clock.at(4), 0).unwrap();
+        assert_eq!(staleness.versions, 2);
+        assert_eq!(staleness.since, clock.at(2));
+    }
+
+    #[test]
+    fn test_unacknowledged_version() {
+        let clock = Clock(Instant::now());
+        let log = WriteLog::default();
+        let first = log.issue(KEY, 0, clock.at(0));
+        log.acknowledge(KEY, first, clock.at(1));
+        let failed = log.issue(KEY, 0, clock.at(2));
+        assert!(read(&log, 1, clock.at(3), failed).is_none());
+        let third = log.issue(KEY, 0, clock.at(4));
+        log.acknowledge(KEY, third, clock.at(5));
+        // The failed update may have been applied after the third.
+        assert!(read(&log, 1, clock.at(6), failed).is_none());
+        assert_eq!(read(&log, 1, clock.at(6), first).unwrap().versions, 1);
+    }
+
+    #[test]
+    fn test_dropped_version() {
+        let clock = Clock(Instant::now());
+        let log = WriteLog::default();
+        let first = log.issue(KEY, 0, clock.at(0));
+        log.acknowledge(KEY, first, clock.at(1));
+        let second = log.issue(KEY, 0, clock.at(2));
+        log.acknowledge(KEY, second, clock.at(3));
+        let third = log.issue(KEY, 0, clock.at(4));
+        log.acknowledge(KEY, third, clock.at(5));
+        assert_eq!(logged(&log), vec![second, third]);
+        let staleness = read(&log, 1, clock.at(6), first).unwrap();
+        assert_eq!(staleness.versions, 2);
+        assert_eq!(staleness.since, clock.at(3));
+        assert!(read(&log, 1, clock.at(6), third).is_none());
+    }
+
+    #[test]
+    fn test_reads_in_flight() {
+        let clock = Clock(Instant::now());
+        let log = WriteLog::default();
+        let first = log.issue(KEY, 0, clock.at(0));
+        log.acknowledge(KEY, first, clock.at(1));
+        let in_flight = log.start_read(KEY, clock.at(2));
+        let second = log.issue(KEY, 0, clock.at(3));
+        log.acknowledge(KEY, second, clock.at(4));
+        let third = log.issue(KEY, 0, clock.at(5));
+        log.acknowledge(KEY, third, clock.at(6));
+        // The read in flight may return any of the updates.
+        assert_eq!(logged(&log), vec![first, second, third]);
+        assert!(log.finish_read(KEY, 1, in_flight, Some(first)).is_none());
+        let fourth = log.issue(KEY, 0, clock.at(7));
+        log.acknowledge(KEY, fourth, clock.at(8));
+        assert_eq!(logged(&log), vec![third, fourth]);
+    }
+
+    #[test]
+    fn test_evicted_version() {
+        let clock = Clock(Instant::now());
+        let log = WriteLog::default();
+        let first = log.issue(KEY, 0, clock.at(0));
+        log.acknowledge(KEY, first, clock.at(1));
+        let in_flight = log.start_read(KEY, clock.at(1));
+        for i in 0..MAX_WRITES as u64 {
+            let version = log.issue(KEY, 0, clock.at(2 * i + 2));
+            log.acknowledge(KEY, version, clock.at(2 * i + 3));
+        }
+        assert_eq!(logged(&log).len(), MAX_WRITES);
+        assert!(!logged(&log).contains(&first));
+        log.finish_read(KEY, 1, in_flight, Some(first));
+        let start = clock.at(2 * MAX_WRITES as u64 + 2);
+        let staleness = read(&log, 1, start, first).unwrap();
+        assert_eq!(staleness.versions, MAX_WRITES as u64);
+    }
+
+    #[test]
+    fn test_read_your_writes() {
+        let clock = Clock(Instant::now());
+        let log = WriteLog::default();
+        let version = log.issue(KEY, 1, clock.at(0));
+        log.acknowledge(KEY, version, clock.at(1));
+        assert!(read(&log, 1, clock.at(2), 0).unwrap().own_write);
+        assert!(!read(&log, 2, clock.at(2), 0).unwrap().own_write);
+        assert!(read(&log, 1, clock.at(2), version).is_none());
+    }
+}
@@ -0,0 +1,968 @@
+use std::io::Write as _;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use anyhow::Result;
+use futures::future::{BoxFuture, FutureExt};
+
+use super::{Operation, Workload, WorkloadClient};
+use crate::core::db::{AsyncDb, Db, KvPair, Record};
+use crate::core::generator::*;
+use crate::core::measurement::Measurements;
+use crate::core::properties::*;
+
+/// The core YCSB workload of reads, updates, inserts, scans and
+/// read-modify-writes over the records of a table.
+///
+/// The key space is shared by all the clients of the workload: the keys of
+/// the load phase and the keys inserted in the run phase are drawn from
+/// shared counters, and the keys of the other operations from a shared
+/// request generator.
+pub struct CoreWorkload {
+    props: Properties,
+    table_name: String,
+    keys: KeyBuilder,
+
+    key_sequence: Arc<CounterGenerator>,
+    insert_key_sequence: Arc<AcknowledgedCounterGenerator>,
+    request_generator: Arc<dyn Generator<u64>>,
+}
+
+impl CoreWorkload {
+    pub fn new(props: &Properties) -> Result<Self> {
+        let insert_start = props.get_insert_start();
+        let insert_count = props.get_insert_count();
+        let record_count = props.get_record_count();
+        if insert_start + insert_count > record_count {
+            return Err(anyhow!(
+                "insertstart {} + insertcount {} exceeds recordcount {}",
+                insert_start,
+                insert_count,
+                record_count
+            ));
+        }
+        if record_count == 0 {
+            // The keys of the run phase are drawn from the loaded records.
+            for property in &[
+                READ_PROPORTION_PROPERTY,
+                UPDATE_PROPORTION_PROPERTY,
+                SCAN_PROPORTION_PROPERTY,
+                READMODIFYWRITE_PROPORTION_PROPERTY,
+            ] {
+                let proportion: f64 = props.get_property(property).parse()?;
+                if proportion > 0.0 {
+                    return Err(anyhow!(
+                        "{} {} requires a recordcount above 0",
+                        property,
+                        proportion
+                    ));
+                }
+            }
+        }
+        let data_integrity: bool = props.get_property(DATA_INTEGRITY_PROPERTY).parse()?;
+        if data_integrity {
+            if props.get_property(FIELD_LENGTH_DISTRIBUTION_PROPERTY) != "constant" {
+                return Err(anyhow!(
+                    "data integrity requires a constant field length distribution"
+                ));
+            }
+            // The values start with their `key:field`, which tells the
+            // records of different keys apart.
+            let field_len: usize = props.get_property(FIELD_LENGTH_PROPERTY).parse()?;
+            let field_count: u64 = props.get_property(FIELD_COUNT_PROPERTY).parse()?;
+            let field_name_len = format!("field{}", field_count.saturating_sub(1)).len();
+            let min_len = max_key_len(props) + 1 + field_name_len;
+            if field_len < min_len {
+                return Err(anyhow!(
+                    "data integrity requires a fieldlength of at least {}, got {}",
+                    min_len,
+                    field_len
+                ));
+            }
+        }
+        let insert_order = props.get_property(INSERT_ORDER_PROPERTY);
+        let ordered = match insert_order.as_str() {
+            "ordered" => true,
+            "hashed" => false,
+            _ => return Err(anyhow!("unsupported insert order {}", insert_order)),
+        };
+        let key_format = props.get_property(KEY_FORMAT_PROPERTY);
+        let format = match key_format.as_str() {
+            "decimal" => KeyFormat::Decimal,
+            "hex" => KeyFormat::Hex,
+            "binary" => KeyFormat::Binary,
+            _ => return Err(anyhow!("unsupported key format {}", key_format)),
+        };
+        // Without records, the run phase only inserts and never draws a key.
+        let request_generator: Arc<dyn Generator<u64>> = if record_count == 0 {
+            Arc::new(ConstantGenerator::new(0))
+        } else {
+            Arc::from(props.get_request_generator()?)
+        };
+        Ok(Self {
+            props: props.clone(),
+            table_name: props.get_property(TABLENAME_PROPERTY),
+            keys: KeyBuilder {
+                prefix: props.get_property(KEY_PREFIX_PROPERTY),
+                zero_padding: props.get_zero_padding(),
+                format,
+                ordered,
+            },
+
+            key_sequence: Arc::new(CounterGenerator::new(insert_start)),
+            insert_key_sequence: Arc::new(AcknowledgedCounterGenerator::new(record_count)),
+            request_generator,
+        })
+    }
+
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    /// Builds the smallest of the first `count` keys of the load phase into
+    /// `key`, where a scan of all their records starts.
+    pub fn build_first_key(&self, count: u64, key: &mut Vec<u8>) {
+        self.keys.build(0, key);
+        if self.keys.ordered {
+            return;
+        }
+        let mut next = Vec::new();
+        for num in 1..count {
+            self.keys.build(num, &mut next);
+            if next < *key {
+                std::mem::swap(key, &mut next);
+            }
+        }
+    }
+
+    /// Creates the state of a client, sharing the key space of the workload.
+    pub fn init_core_client(&self, measurements: Measurements) -> Result<CoreClient> {
+        let props = &self.props;
+        let field_count: u64 = props.get_property(FIELD_COUNT_PROPERTY).parse()?;
+
+        let op_chooser = props.get_operation_generator()?;
+        let field_len_generator = props.get_field_len_generator()?;
+        let scan_len_chooser = props.get_scan_length_generator()?;
+        let field_chooser = UniformGenerator::new().max(field_count - 1);
+
+        let read_all_fields: bool = props.get_property(READ_ALL_FIELDS_PROPERTY).parse()?;
+        let write_all_fields: bool = props.get_property(WRITE_ALL_FIELDS_PROPERTY).parse()?;
+        let data_integrity: bool = props.get_property(DATA_INTEGRITY_PROPERTY).parse()?;
+        let read_batch_size = props.get_read_batch_size()?;
+
+        Ok(CoreClient {
+            table_name: self.table_name.clone(),
+
+            keys: self.keys.clone(),
+            read_all_fields,
+            write_all_fields,
+            data_integrity,
+            read_batch_size,
+
+            key_sequence: self.key_sequence.clone(),
+            op_chooser,
+            request_generator: self.request_generator.clone(),
+            insert_key_sequence: self.insert_key_sequence.clone(),
+
+            scan_len_chooser,
+            field_chooser,
+            field_len_generator,
+            value_generator: props.get_value_generator()?,
+
+            fields: (0..field_count).map(|i| format!("field{}", i)).collect(),
+            buffers: Mutex::new(Vec::new()),
+
+            measurements,
+        })
+    }
+}
+
+impl Workload for CoreWorkload {
+    fn init_client(
+        &self,
+        _: usize,
+        _: usize,
+        measurements: Measurements,
+    ) -> Result<Box<dyn WorkloadClient>> {
+        Ok(Box::new(self.init_core_client(measurements)?))
+    }
+}
+
+/// The encoding of the number of a key after the key prefix.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyFormat {
+    /// The decimal digits of the number, padded with zeros up to
+    /// `zeropadding` digits.
+    Decimal,
+    /// The 16 hex digits of the number, selected by `keyformat=hex`. The
+    /// keys have a fixed width and sort like their numbers.
+    Hex,
+    /// The 8 bytes of the number in big-endian order, selected by
+    /// `keyformat=binary`. The keys have a fixed width and sort like their
+    /// numbers.
+    Binary,
+}
+
+/// The state of a client of the `CoreWorkload`.
+pub struct CoreClient {
+    table_name: String,
+
+    keys: KeyBuilder,
+    read_all_fields: bool,
+    write_all_fields: bool,
+    data_integrity: bool,
+    read_batch_size: u64,
+
+    key_sequence: Arc<CounterGenerator>,
+    op_chooser: DiscreteGenerator<Operation>,
+    request_generator: Arc<dyn Generator<u64>>,
+    insert_key_sequence: Arc<AcknowledgedCounterGenerator>,
+
+    scan_len_chooser: Box<dyn Generator<u64>>,
+    field_chooser: UniformGenerator,
+    field_len_generator: Box<dyn Generator<u64>>,
+    value_generator: Box<dyn ValueGenerator>,
+
+    fields: Vec<String>,
+    buffers: Mutex<Vec<Buffers>>,
+
+    measurements: Measurements,
+}
+
+/// The key and values an operation builds, reused by the following
+/// operations of the client so that they do not allocate.
+#[derive(Default)]
+pub struct Buffers {
+    pub key: Vec<u8>,
+    pub values: Vec<KvPair>,
+    records: Vec<Record>,
+    keys: Vec<Vec<u8>>,
+    expected: Vec<u8>,
+}
+
+/// Builds the key of a record from its number.
+#[derive(Clone)]
+struct KeyBuilder {
+    prefix: String,
+    zero_padding: usize,
+    format: KeyFormat,
+    /// Whether the numbers are used in order rather than hashed.
+    ordered: bool,
+}
+
+impl KeyBuilder {
+    fn build(&self, mut num: u64, key: &mut Vec<u8>) {
+        if !self.ordered {
+            num = fxhash::hash64(&num);
+        }
+        key.clear();
+        key.extend_from_slice(self.prefix.as_bytes());
+        let _ = match self.format {
+            KeyFormat::Decimal => write!(key, "{:0width$}", num, width = self.zero_padding),
+            KeyFormat::Hex => write!(key, "{:016x}", num),
+            KeyFormat::Binary => key.write_all(&num.to_be_bytes()),
+        };
+    }
+}
+
+impl CoreClient {
+    fn build_key_name(&self, num: u64, key: &mut Vec<u8>) {
+        self.keys.build(num, key)
+    }
+
+    /// Builds the next key of the load phase into `key`.
+    pub fn next_sequence_key(&self, key: &mut Vec<u8>) {
+        self.build_key_name(self.key_sequence.next(), key)
+    }
+
+    /// Returns the number of the next key to insert in the run phase, which
+    /// must be acknowledged by `acknowledge_insert` once inserted.
+    pub fn next_transaction_insert_keynum(&self) -> u64 {
+        self.insert_key_sequence.next()
+    }
+
+    /// Acknowledges the insert of the key `keynum`, making the key available
+    /// to the other operations once all the keys before it are inserted.
+    pub fn acknowledge_insert(&self, keynum: u64) {
+        self.insert_key_sequence.acknowledge(keynum)
+    }
+
+    /// Builds the key of the next operation of the run phase into `key`.
+    pub fn next_transaction_key(&self, key: &mut Vec<u8>) {
+        let mut num = self.request_generator.next();
+        while num > self.insert_key_sequence.last() {
+            num = self.request_generator.next();
+        }
+        self.build_key_name(num, key)
+    }
+
+    /// Builds the keys of the next read of the run phase into `keys`,
+    /// returning them, see `next_transaction_key`.
+    fn next_read_keys<'a>(&self, keys: &'a mut Vec<Vec<u8>>) -> &'a [Vec<u8>] {
+        let count = self.read_batch_size as usize;
+        if keys.len() < count {
+            keys.resize_with(count, Default::default);
+        }
+        for key in &mut keys[..count] {
+            self.next_transaction_key(key);
+        }
+        &keys[..count]
+    }
+
+    fn next_field_name(&self) -> &str {
+        &self.fields[self.field_chooser.next() as usize]
+    }
+
+    pub fn next_operation(&self) -> Operation {
+        self.op_chooser.next()
+    }
+
+    pub fn next_table(&self) -> &str {
+        &self.table_name
+    }
+
+    pub fn next_scan_length(&self) -> u64 {
+        self.scan_len_chooser.next()
+    }
+
+    pub fn read_fields(&self) -> &[String] {
+        if self.read_all_fields {
+            &self.fields
+        } else {
+            let field = self.field_chooser.next() as usize;
+            std::slice::from_ref(&self.fields[field])
+        }
+    }
+
+    pub fn write_all_fields(&self) -> bool {
+        self.write_all_fields
+    }
+
+    /// Returns whether the values are derived from their keys and fields,
+    /// so that the values read can be verified.
+    pub fn data_integrity(&self) -> bool {
+        self.data_integrity
+    }
+
+    /// Takes the buffers of an operation from the pool of the client, which
+    /// are returned by `put_buffers` once the operation finished.
+    pub fn take_buffers(&self) -> Buffers {
+        self.buffers.lock().unwrap().pop().unwrap_or_default()
+    }
+
+    pub fn put_buffers(&self, buffers: Buffers) {
+        self.buffers.lock().unwrap().push(buffers);
+    }
+
+    /// Builds all the fields of `key` into `values`.
+    pub fn build_values(&self, key: &[u8], values: &mut Vec<KvPair>) {
+        values.resize_with(self.fields.len(), Default::default);
+        for ((field, value), name) in values.iter_mut().zip(&self.fields) {
+            set_field(field, name);
+            self.build_value(key, field, value);
+        }
+    }
+
+    /// Builds a single random field of `key` into `values`.
+    pub fn build_update(&self, key: &[u8], values: &mut Vec<KvPair>) {
+        values.resize_with(1, Default::default);
+        let (field, value) = &mut values[0];
+        set_field(field, self.next_field_name());
+        self.build_value(key, field, value);
+    }
+
+    fn build_value(&self, key: &[u8], field: &str, value: &mut Vec<u8>) {
+        let len = self.field_len_generator.next() as usize;
+        if self.data_integrity {
+            return build_deterministic_value(key, field, len, value);
+        }
+        self.value_generator.next_value(value, len)
+    }
+
+    /// Checks the values read from `key`, which must be the `fields`
+    /// requested, or all the fields if `fields` is empty, building the
+    /// expected values into `expected`.
+    pub fn verify_row(
+        &self,
+        key: &[u8],
+        fields: &[String],
+        values: &[KvPair],
+        expected: &mut Vec<u8>,
+    ) -> bool {
+        let fields = if fields.is_empty() {
+            &self.fields
+        } else {
+            fields
+        };
+        values.len() == fields.len()
+            && fields
+                .iter()
+                .all(|field| values.iter().any(|(name, _)| name == field))
+            && values.iter().all(|(field, value)| {
+                build_deterministic_value(
+                    key,
+                    field,
+                    self.field_len_generator.next() as usize,
+                    expected,
+                );
+                value == expected
+            })
+    }
+
+    pub fn measurements(&self) -> &Measurements {
+        &self.measurements
+    }
+
+    /// Verifies the values read from `key` when the data integrity is
+    /// checked. The verification is measured as `VERIFY`, with an error for a
+    /// mismatch.
+    fn verify(&self, key: &[u8], fields: &[String], values: &[KvPair], expected: &mut Vec<u8>) {
+        if !self.data_integrity {
+            return;
+        }
+        let start = Instant::now();
+        let ok = self.verify_row(key, fields, values, expected);
+        self.measurements.measure("VERIFY", start.elapsed(), ok);
+    }
+
+    /// Verifies the rows of a `Db::multi_get` of `keys` like `verify`, a
+    /// missing row being an error.
+    fn verify_rows(
+        &self,
+        keys: &[Vec<u8>],
+        fields: &[String],
+        rows: &[Vec<KvPair>],
+        expected: &mut Vec<u8>,
+    ) {
+        if !self.data_integrity {
+            return;
+        }
+        for (i, key) in keys.iter().enumerate() {
+            let start = Instant::now();
+            let ok = match rows.get(i) {
+                Some(row) => self.verify_row(key, fields, row, expected),
+                None => false,
+            };
+            self.measurements.measure("VERIFY", start.elapsed(), ok);
+        }
+    }
+
+    /// Verifies the records of a scan of `count` records from `start` like
+    /// `verify`, a record out of the range of the scan being an error.
+    fn verify_scan(
+        &self,
+        start: &[u8],
+        count: u64,
+        fields: &[String],
+        records: &[Record],
+        expected: &mut Vec<u8>,
+    ) {
+        if !self.data_integrity {
+            return;
+        }
+        let mut previous: Option<&[u8]> = None;
+        for (i, (key, values)) in records.iter().enumerate() {
+            let timer = Instant::now();
+            let in_range = (i as u64) < count
+                && previous.map_or(start <= key.as_slice(), |previous| {
+                    previous < key.as_slice()
+                });
+            let ok = in_range && self.verify_row(key, fields, values, expected);
+            self.measurements.measure("VERIFY", timer.elapsed(), ok);
+            previous = Some(key);
+        }
+    }
+
+    /// Inserts the next `count` records of the load phase, in one batch if
+    /// `count` is above one, building the values of every key with
+    /// `build_values`.
+    pub fn insert_records<F>(&self, db: &dyn Db, count: u64, build_values: F) -> Result<()>
+    where
+        F: Fn(&[u8], &mut Vec<KvPair>),
+    {
+        let mut buffers = self.take_buffers();
+        let table = self.next_table();
+        let result = if count > 1 {
+            let records = self.build_records(count, build_values, &mut buffers.records);
+            db.batch_insert(table, records)
+        } else {
+            self.next_sequence_key(&mut buffers.key);
+            build_values(&buffers.key, &mut buffers.values);
+            db.insert(table, &buffers.key, &buffers.values)
+        };
+        self.put_buffers(buffers);
+        result
+    }
+
+    /// Inserts the next `count` records of the load phase, see
+    /// `insert_records`.
+    pub async fn insert_records_async<F>(
+        &self,
+        db: &dyn AsyncDb,
+        count: u64,
+        build_values: F,
+    ) -> Result<()>
+    where
+        F: Fn(&[u8], &mut Vec<KvPair>),
+    {
+        let mut buffers = self.take_buffers();
+        let table = self.next_table();
+        let result = if count > 1 {
+            let records = self.build_records(count, build_values, &mut buffers.records);
+            db.batch_insert(table, records).await
+        } else {
+            self.next_sequence_key(&mut buffers.key);
+            build_values(&buffers.key, &mut buffers.values);
+            db.insert(table, &buffers.key, &buffers.values).await
+        };
+        self.put_buffers(buffers);
+        result
+    }
+
+    /// Builds the next `count` records of the load phase into `records`,
+    /// returning them.
+    fn build_records<'a, F>(
+        &self,
+        count: u64,
+        build_values: F,
+        records: &'a mut Vec<Record>,
+    ) -> &'a [Record]
+    where
+        F: Fn(&[u8], &mut Vec<KvPair>),
+    {
+        let count = count as usize;
+        if records.len() < count {
+            records.resize_with(count, Default::default);
+        }
+        for (key, values) in &mut records[..count] {
+            self.next_sequence_key(key);
+            build_values(key, values);
+        }
+        &records[..count]
+    }
+
+    /// Acknowledges the inserts of the key numbers `inserted` by a
+    /// transaction if it committed, or else releases them to be inserted
+    /// again, so that the keys of an aborted transaction are never read.
+    pub fn finish_inserts(&self, inserted: &[u64], committed: bool) {
+        for keynum in inserted {
+            if committed {
+                self.acknowledge_insert(*keynum);
+            } else {
+                self.insert_key_sequence.release(*keynum);
+            }
+        }
+    }
+
+    /// Runs `op`, measuring a read-modify-write as a whole.
+    pub fn do_measured_operation(&self, db: &dyn Db, op: Operation) -> Result<()> {
+        self.do_measured_operation_in(db, op, None)
+    }
+
+    /// Runs `op` in a transaction, see `do_measured_operation`. The key
+    /// number of an insert is pushed to `inserted` rather than acknowledged,
+    /// see `finish_inserts`.
+    pub fn do_transaction_operation(
+        &self,
+        db: &dyn Db,
+        op: Operation,
+        inserted: &mut Vec<u64>,
+    ) -> Result<()> {
+        self.do_measured_operation_in(db, op, Some(inserted))
+    }
+
+    fn do_measured_operation_in(
+        &self,
+        db: &dyn Db,
+        op: Operation,
+        inserted: Option<&mut Vec<u64>>,
+    ) -> Result<()> {
+        let mut buffers = self.take_buffers();
+        let result = match op {
+            Operation::ReadModifyWrite => {
+                let start = Instant::now();
+                let result = self.do_operation(db, op.clone(), &mut buffers, inserted);
+                self.measurements
+                    .measure(op.name(), start.elapsed(), result.is_ok());
+                result
+            }
+            _ => self.do_operation(db, op, &mut buffers, inserted),
+        };
+        self.put_buffers(buffers);
+        result
+    }
+
+    fn do_operation(
+        &self,
+        db: &dyn Db,
+        op: Operation,
+        buffers: &mut Buffers,
+        inserted: Option<&mut Vec<u64>>,
+    ) -> Result<()> {
+        let table = self.next_table();
+        let Buffers {
+            key,
+            values,
+            keys,
+            expected,
+            ..
+        } = buffers;
+
+        match op {
+            Operation::Read if self.read_batch_size > 1 => {
+                let keys = self.next_read_keys(keys);
+                let fields = self.read_fields();
+                let rows = db.multi_get(table, keys, fields)?;
+                self.verify_rows(keys, fields, &rows, expected);
+                Ok(())
+            }
+            Operation::Read => {
+                self.next_transaction_key(key);
+                let fields = self.read_fields();
+                let row = db.read(table, key, fields)?;
+                self.verify(key, fields, &row, expected);
+                Ok(())
+            }
+            Operation::Update => {
+                self.next_transaction_key(key);
+                if self.write_all_fields() {
+                    self.build_values(key, values);
+                } else {
+                    self.build_update(key, values);
+                }
+                db.update(table, key, values)
+            }
+            Operation::Insert => {
+                let keynum = self.next_transaction_insert_keynum();
+                self.build_key_name(keynum, key);
+                self.build_values(key, values);
+                let result = db.insert(table, key, values);
+                match inserted {
+                    Some(inserted) => inserted.push(keynum),
+                    None => self.acknowledge_insert(keynum),
+                }
+                result
+            }
+            Operation::Scan => {
+                self.next_transaction_key(key);
+                let count = self.next_scan_length();
+                let fields = self.read_fields();
+                let records = db.scan(table, key, fields, count)?;
+                self.verify_scan(key, count, fields, &records, expected);
+                Ok(())
+            }
+            Operation::ReadModifyWrite => {
+                self.next_transaction_key(key);
+                let fields = self.read_fields();
+                let row = db.read(table, key, fields)?;
+                self.verify(key, fields, &row, expected);
+                if self.write_all_fields() {
+                    self.build_values(key, values);
+                } else {
+                    self.build_update(key, values);
+                }
+                db.update(table, key, values)
+            }
+        }
+    }
+
+    /// Runs `op`, see `do_measured_operation`.
+    pub async fn do_measured_operation_async(&self, db: &dyn AsyncDb, op: Operation) -> Result<()> {
+        self.do_measured_operation_in_async(db, op, None).await
+    }
+
+    /// Runs `op` in a transaction, see `do_transaction_operation`.
+    pub async fn do_transaction_operation_async(
+        &self,
+        db: &dyn AsyncDb,
+        op: Operation,
+        inserted: &mut Vec<u64>,
+    ) -> Result<()> {
+        self.do_measured_operation_in_async(db, op, Some(inserted))
+            .await
+    }
+
+    async fn do_measured_operation_in_async(
+        &self,
+        db: &dyn AsyncDb,
+        op: Operation,
+        inserted: Option<&mut Vec<u64>>,
+    ) -> Result<()> {
+        let mut buffers = self.take_buffers();
+        let result = match op {
+            Operation::ReadModifyWrite => {
+                let start = Instant::now();
+                let result = self
+                    .do_operation_async(db, op.clone(), &mut buffers, inserted)
+                    .await;
+                self.measurements
+                    .measure(op.name(), start.elapsed(), result.is_ok());
+                result
+            }
+            _ => {
+                self.do_operation_async(db, op, &mut buffers, inserted)
+                    .await
+            }
+        };
+        self.put_buffers(buffers);
+        result
+    }
+
+    async fn do_operation_async(
+        &self,
+        db: &dyn AsyncDb,
+        op: Operation,
+        buffers: &mut Buffers,
+        inserted: Option<&mut Vec<u64>>,
+    ) -> Result<()> {
+        let table = self.next_table();
+        let Buffers {
+            key,
+            values,
+            keys,
+            expected,
+            ..
+        } = buffers;
+
+        match op {
+            Operation::Read if self.read_batch_size > 1 => {
+                let keys = self.next_read_keys(keys);
+                let fields = self.read_fields();
+                let rows = db.multi_get(table, keys, fields).await?;
+                self.verify_rows(keys, fields, &rows, expected);
+                Ok(())
+            }
+            Operation::Read => {
+                self.next_transaction_key(key);
+                let fields = self.read_fields();
+                let row = db.read(table, key, fields).await?;
+                self.verify(key, fields, &row, expected);
+                Ok(())
+            }
+            Operation::Update => {
+                self.next_transaction_key(key);
+                if self.write_all_fields() {
+                    self.build_values(key, values);
+                } else {
+                    self.build_update(key, values);
+                }
+                db.update(table, key, values).await
+            }
+            Operation::Insert => {
+                let keynum = self.next_transaction_insert_keynum();
+                self.build_key_name(keynum, key);
+                self.build_values(key, values);
+                let result = db.insert(table, key, values).await;
+                match inserted {
+                    Some(inserted) => inserted.push(keynum),
+                    None => self.acknowledge_insert(keynum),
+                }
+                result
+            }
+            Operation::Scan => {
+                self.next_transaction_key(key);
+                let count = self.next_scan_length();
+                let fields = self.read_fields();
+                let records = db.scan(table, key, fields, count).await?;
+                self.verify_scan(key, count, fields, &records, expected);
+                Ok(())
+            }
+            Operation::ReadModifyWrite => {
+                self.next_transaction_key(key);
+                let fields = self.read_fields();
+                let row = db.read(table, key, fields).await?;
+                self.verify(key, fields, &row, expected);
+                if self.write_all_fields() {
+                    self.build_values(key, values);
+                } else {
+                    self.build_update(key, values);
+                }
+                db.update(table, key, values).await
+            }
+        }
+    }
+}
+
+/// Sets the name of `field` to `name`, reusing its allocation.
+fn set_field(field: &mut String, name: &str) {
+    if field != name {
+        field.clear();
+        field.push_str(name);
+    }
+}
+
+/// Returns the length of the longest key of a workload, see `build_key_name`.
+fn max_key_len(props: &Properties) -> usize {
+    let max = match props.get_property(INSERT_ORDER_PROPERTY).as_str() {
+        "ordered" => props.get_record_count() + props.get_operation_count(),
+        _ => u64::MAX,
+    };
+    let digits = match props.get_property(KEY_FORMAT_PROPERTY).as_str() {
+        "hex" => 16,
+        "binary" => 8,
+        _ => props.get_zero_padding().max(max.to_string().len()),
+    };
+    props.get_property(KEY_PREFIX_PROPERTY).len() + digits
+}
+
+/// Builds the value of `field` of `key` into `value`, which starts with
+/// `key:field` and is padded with hashes of itself up to `len` bytes.
+fn build_deterministic_value(key: &[u8], field: &str, len: usize, value: &mut Vec<u8>) {
+    value.clear();
+    value.extend_from_slice(key);
+    let _ = write!(value, ":{}", field);
+    while value.len() < len {
+        let hash = fxhash::hash64(value.as_slice());
+        let _ = write!(value, ":{}", hash);
+    }
+    value.truncate(len);
+}
+
+impl WorkloadClient for CoreClient {
+    fn do_insert(&self, db: &dyn Db, count: u64) -> Result<()> {
+        self.insert_records(db, count, |key, values| self.build_values(key, values))
+    }
+
+    fn do_transaction(&self, db: &dyn Db) -> (&'static str, Result<()>) {
+        let op = self.next_operation();
+        (op.name(), self.do_measured_operation(db, op))
+    }
+
+    fn do_insert_async<'a>(&'a self, db: &'a dyn AsyncDb, count: u64) -> BoxFuture<'a, Result<()>> {
+        self.insert_records_async(db, count, move |key, values| self.build_values(key, values))
+            .boxed()
+    }
+
+    fn do_transaction_async<'a>(
+        &'a self,
+        db: &'a dyn AsyncDb,
+    ) -> BoxFuture<'a, (&'static str, Result<()>)> {
+        async move {
+            let op = self.next_operation();
+            (op.name(), self.do_measured_operation_async(db, op).await)
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::core::db::DbWrapper;
+    use crate::core::workload::split_ops;
+
+    fn workload(props: &str) -> Result<CoreWorkload> {
+        let props = format!("recordcount=1000\noperationcount=1000\n{}", props);
+        CoreWorkload::new(&Properties::load(props.as_bytes())?)
+    }
+
+    #[test]
+    fn test_short_fields_rejected() {
+        assert!(workload("dataintegrity=true\nfieldlength=30").is_err());
+        assert!(workload("dataintegrity=true\nfieldlength=31").is_ok());
+        assert!(workload("fieldlength=8").is_ok());
+    }
+
+    #[test]
+    fn test_key_formats() -> Result<()> {
+        let key = |props: &str, num: u64| -> Result<Vec<u8>> {
+            let props = format!("insertorder=ordered\n{}", props);
+            let client = workload(&props)?.init_core_client(Measurements::new())?;
+            let mut key = Vec::new();
+            client.build_key_name(num, &mut key);
+            Ok(key)
+        };
+        assert_eq!(key("zeropadding=4", 42)?, b"user0042");
+        assert_eq!(key("keyformat=hex", 42)?, b"user000000000000002a");
+        assert_eq!(
+            key("keyformat=binary\nkeyprefix=k", 0x0102)?,
+            b"k\0\0\0\0\0\0\x01\x02"
+        );
+        assert!(key("keyformat=binary", 255)? < key("keyformat=binary", 256)?);
+        assert!(workload("keyformat=octal").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_partitioned_load() -> Result<()> {
+        let load = |start: u64, count: u64, clients: usize| -> Result<Vec<Vec<u8>>> {
+            let props = format!("insertstart={}\ninsertcount={}", start, count);
+            let workload = workload(&props)?;
+            let mut keys = Vec::new();
+            for i in 0..clients {
+                let client = workload.init_core_client(Measurements::new())?;
+                for _ in 0..split_ops(count, clients, i) {
+                    let mut key = Vec::new();
+                    client.next_sequence_key(&mut key);
+                    keys.push(key);
+                }
+            }
+            Ok(keys)
+        };
+        let mut all = load(0, 1000, 1)?;
+        all.sort();
+        all.dedup();
+        assert_eq!(all.len(), 1000);
+
+        // The processes load disjoint ranges, each with several clients.
+        let mut keys = Vec::new();
+        for &(start, count) in &[(0, 300), (300, 451), (751, 249)] {
+            keys.extend(load(start, count, 3)?);
+        }
+        keys.sort();
+        assert_eq!(keys, all);
+        assert!(workload("insertstart=751\ninsertcount=250").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_row() -> Result<()> {
+        let workload = workload("dataintegrity=true\nkeyprefix=us:er\nfieldlength=40")?;
+        let client = workload.init_core_client(Measurements::new())?;
+        let (mut key, mut other, mut values) = (Vec::new(), Vec::new(), Vec::new());
+        client.build_key_name(1, &mut key);
+        client.build_key_name(2, &mut other);
+        client.build_values(&key, &mut values);
+        let mut expected = Vec::new();
+        let fields = &client.fields;
+        assert!(client.verify_row(&key, fields, &values, &mut expected));
+        assert!(client.verify_row(&key, &[], &values, &mut expected));
+        assert!(!client.verify_row(&other, fields, &values, &mut expected));
+
+        // A missing or an extra field is an error.
+        let field = &fields[3..4];
+        assert!(client.verify_row(&key, field, &values[3..4], &mut expected));
+        assert!(!client.verify_row(&key, field, &values[2..3], &mut expected));
+        assert!(!client.verify_row(&key, field, &values[3..5], &mut expected));
+        assert!(!client.verify_row(&key, field, &[], &mut expected));
+        assert!(!client.verify_row(&key, fields, &values[1..], &mut expected));
+        let mut duplicated = values.clone();
+        duplicated[1] = duplicated[0].clone();
+        assert!(!client.verify_row(&key, fields, &duplicated, &mut expected));
+
+        values[0].1[0] ^= 1;
+        assert!(!client.verify_row(&key, fields, &values, &mut expected));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_batch() -> Result<()> {
+        let workload = workload("recordcount=10\nreadbatchsize=4\ndataintegrity=true")?;
+        let measurements = Measurements::new();
+        let client = workload.init_core_client(measurements.clone())?;
+        let db = crate::db::create_db("btree", &Properties::default())?;
+        client.do_insert(db.as_ref(), 10)?;
+        let db = DbWrapper::new(db, measurements.clone());
+        client.do_measured_operation(&db, Operation::Read)?;
+        let infos = measurements.info();
+        let count = |name: &str| {
+            let (_, info) = infos.iter().find(|(n, _)| n == name).unwrap();
+            (info.count, info.errors)
+        };
+        assert_eq!(count("MULTI-GET"), (1, 0));
+        assert_eq!(count("READ"), (4, 0));
+        assert_eq!(count("VERIFY"), (4, 0));
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+use std::borrow::Cow;
+use std::time::Instant;
+
+use anyhow::Result;
+use futures::future::{BoxFuture, FutureExt};
+
+use super::{in_transaction, in_transaction_async, CoreClient, CoreWorkload};
+use super::{Workload, WorkloadClient};
+use crate::core::db::{AsyncDb, Db};
+use crate::core::generator::Generator;
+use crate::core::measurement::Measurements;
+use crate::core::properties::Properties;
+
+/// A YCSB+T style workload, where every operation is a transaction of
+/// several operations of the core workload, measured as `TRANSACTION`. The
+/// keys of the operations are drawn independently, so a transaction may
+/// access a key more than once. The keys inserted by a transaction are only
+/// read by other operations once it committed.
+pub struct TransactionalWorkload {
+    core: CoreWorkload,
+    props: Properties,
+}
+
+impl TransactionalWorkload {
+    pub fn new(props: &Properties) -> Result<Self> {
+        props.get_transaction_size_generator()?;
+        Ok(Self {
+            core: CoreWorkload::new(props)?,
+            props: props.clone(),
+        })
+    }
+}
+
+impl Workload for TransactionalWorkload {
+    fn init_client(
+        &self,
+        _: usize,
+        _: usize,
+        measurements: Measurements,
+    ) -> Result<Box<dyn WorkloadClient>> {
+        Ok(Box::new(TransactionalClient {
+            core: self.core.init_core_client(measurements)?,
+            transaction_size: self.props.get_transaction_size_generator()?,
+        }))
+    }
+}
+
+/// The state of a client of the `TransactionalWorkload`.
+pub struct TransactionalClient {
+    core: CoreClient,
+    transaction_size: Box<dyn Generator<u64>>,
+}
+
+impl TransactionalClient {
+    fn do_multi_key_transaction(
+        &self,
+        db: &dyn Db,
+        size: u64,
+        inserted: &mut Vec<u64>,
+    ) -> Result<()> {
+        for _ in 0..size {
+            let op = self.core.next_operation();
+            self.core.do_transaction_operation(db, op, inserted)?;
+        }
+        Ok(())
+    }
+
+    async fn do_multi_key_transaction_async(
+        &self,
+        db: &dyn AsyncDb,
+        size: u64,
+        inserted: &mut Vec<u64>,
+    ) -> Result<()> {
+        for _ in 0..size {
+            let op = self.core.next_operation();
+            self.core
+                .do_transaction_operation_async(db, op, inserted)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+impl WorkloadClient for TransactionalClient {
+    fn do_insert(&self, db: &dyn Db, count: u64) -> Result<()> {
+        self.core.do_insert(db, count)
+    }
+
+    fn do_transaction(&self, db: &dyn Db) -> (&'static str, Result<()>) {
+        let size = self.transaction_size.next();
+        let measurements = self.core.measurements();
+        let mut inserted = Vec::new();
+        let start = Instant::now();
+        let result = in_transaction(db, measurements, |db| {
+            self.do_multi_key_transaction(db, size, &mut inserted)
+        });
+        measurements.measure("TRANSACTION", start.elapsed(), result.is_ok());
+        self.core.finish_inserts(&inserted, result.is_ok());
+        ("TRANSACTION", result)
+    }
+
+    fn do_insert_async<'a>(&'a self, db: &'a dyn AsyncDb, count: u64) -> BoxFuture<'a, Result<()>> {
+        self.core.do_insert_async(db, count)
+    }
+
+    fn do_transaction_async<'a>(
+        &'a self,
+        db: &'a dyn AsyncDb,
+    ) -> BoxFuture<'a, (&'static str, Result<()>)> {
+        async move {
+            let size = self.transaction_size.next();
+            let measurements = self.core.measurements();
+            let mut inserted = Vec::new();
+            let start = Instant::now();
+            let inserted_ref = &mut inserted;
+            let result = in_transaction_async(db, measurements, |db| async move {
+                self.do_multi_key_transaction_async(db.as_db(), size, inserted_ref)
+                    .await
+            })
+            .await;
+            measurements.measure("TRANSACTION", start.elapsed(), result.is_ok());
+            self.core.finish_inserts(&inserted, result.is_ok());
+            ("TRANSACTION", result)
+        }
+        .boxed()
+    }
+
+    fn intended_name(&self, _: &'static str) -> Cow<'static, str> {
+        Cow::Borrowed("INTENDED-TRANSACTION")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use crate::core::db::{KvPair, Record, Transaction};
+
+    /// A `Db` whose transactions only insert, and commit if `commit`.
+    struct InsertDb {
+        commit: bool,
+    }
+
+    impl Db for InsertDb {
+        fn init(&self) {}
+
+        fn close(&self) {}
+
+        fn start(&self) -> Result<Option<Arc<dyn Transaction>>> {
+            Ok(Some(Arc::new(InsertDb {
+                commit: self.commit,
+            })))
+        }
+
+        fn read(&self, _: &str, _: &[u8], _: &[String]) -> Result<Vec<KvPair>> {
+            Err(anyhow!("read not supported"))
+        }
+
+        fn scan(&self, _: &str, _: &[u8], _: &[String], _: u64) -> Result<Vec<Record>> {
+            Err(anyhow!("scan not supported"))
+        }
+
+        fn update(&self, _: &str, _: &[u8], _: &[KvPair]) -> Result<()> {
+            Err(anyhow!("update not supported"))
+        }
+
+        fn insert(&self, _: &str, _: &[u8], _: &[KvPair]) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Transaction for InsertDb {
+        fn as_db(&self) -> &dyn Db {
+            self
+        }
+
+        fn commit(&self) -> Result<()> {
+            if self.commit {
+                Ok(())
+            } else {
+                Err(anyhow!("conflict"))
+            }
+        }
+
+        fn abort(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_aborted_inserts_released() -> Result<()> {
+        let props = "recordcount=10\ntransactionsize=2\n\
+                     readproportion=0\nupdateproportion=0\ninsertproportion=1";
+        let props = Properties::load(props.as_bytes())?;
+        let workload = TransactionalWorkload::new(&props)?;
+        let client = TransactionalClient {
+            core: workload.core.init_core_client(Measurements::new())?,
+            transaction_size: props.get_transaction_size_generator()?,
+        };
+
+        assert!(client
+            .do_transaction(&InsertDb { commit: false })
+            .1
+            .is_err());
+        // The keys of the aborted transaction are inserted again.
+        assert!(client.do_transaction(&InsertDb { commit: true }).1.is_ok());
+        assert_eq!(client.core.next_transaction_insert_keynum(), 12);
+        Ok(())
+    }
+}
@@ -1,17 +1,106 @@
 mod btree;
 mod skiplist;
 
-use std::sync::Arc;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
 
 use anyhow::Result;
 
-use crate::core::db::Db;
+use crate::core::db::{AsyncDb, Db, KvPair, SyncDbAdapter};
+use crate::core::properties::Properties;
 
-pub fn create_db(name: &str) -> Result<Arc<dyn Db>> {
-    let db: Arc<dyn Db> = match name {
-        "btree" => Arc::new(btree::BTreeDb::new()),
-        "skiplist" => Arc::new(skiplist::SkiplistDb::new()),
-        _ => return Err(anyhow!("unsupported database {}", name)),
+type DbFactory = Arc<dyn Fn(&Properties) -> Result<Arc<dyn Db>> + Send + Sync>;
+
+type AsyncDbFactory = Arc<dyn Fn(&Properties) -> Result<Arc<dyn AsyncDb>> + Send + Sync>;
+
+lazy_static! {
+    static ref DB_FACTORIES: RwLock<BTreeMap<String, DbFactory>> = {
+        let mut factories: BTreeMap<String, DbFactory> = BTreeMap::new();
+        factories.insert(
+            "btree".to_string(),
+            Arc::new(|_: &Properties| Ok(Arc::new(btree::BTreeDb::new()) as Arc<dyn Db>)),
+        );
+        factories.insert(
+            "skiplist".to_string(),
+            Arc::new(|_: &Properties| Ok(Arc::new(skiplist::SkiplistDb::new()) as Arc<dyn Db>)),
+        );
+        RwLock::new(factories)
     };
-    Ok(db)
+    static ref ASYNC_DB_FACTORIES: RwLock<BTreeMap<String, AsyncDbFactory>> =
+        RwLock::new(BTreeMap::new());
+}
+
+/// Registers the binding `name`, replacing any binding registered with the
+/// same name. The factory configures the binding from the workload
+/// properties.
+pub fn register_db<F>(name: &str, factory: F)
+where
+    F: Fn(&Properties) -> Result<Arc<dyn Db>> + Send + Sync + 'static,
+{
+    DB_FACTORIES
+        .write()
+        .unwrap()
+        .insert(name.to_string(), Arc::new(factory));
+}
+
+/// Registers the non-blocking binding `name`, which is preferred over a
+/// synchronous binding of the same name by `create_async_db`.
+pub fn register_async_db<F>(name: &str, factory: F)
+where
+    F: Fn(&Properties) -> Result<Arc<dyn AsyncDb>> + Send + Sync + 'static,
+{
+    ASYNC_DB_FACTORIES
+        .write()
+        .unwrap()
+        .insert(name.to_string(), Arc::new(factory));
+}
+
+/// Returns the sorted names of all the registered bindings.
+pub fn db_names() -> Vec<String> {
+    let mut names: Vec<String> = DB_FACTORIES.read().unwrap().keys().cloned().collect();
+    names.extend(ASYNC_DB_FACTORIES.read().unwrap().keys().cloned());
+    names.sort();
+    names.dedup();
+    names
+}
+
+pub fn create_db(name: &str, props: &Properties) -> Result<Arc<dyn Db>> {
+    let factory = DB_FACTORIES.read().unwrap().get(name).cloned();
+    match factory {
+        Some(factory) => factory(props),
+        None => Err(anyhow!("unsupported database {}", name)),
+    }
+}
+
+/// Creates the non-blocking binding `name`. The synchronous bindings are
+/// adapted by running their calls on the blocking thread pool.
+pub fn create_async_db(name: &str, props: &Properties) -> Result<Arc<dyn AsyncDb>> {
+    let factory = ASYNC_DB_FACTORIES.read().unwrap().get(name).cloned();
+    match factory {
+        Some(factory) => factory(props),
+        None => Ok(Arc::new(SyncDbAdapter::new(create_db(name, props)?))),
+    }
+}
+
+/// Returns the `fields` of a record, or all its fields if `fields` is empty.
+fn select_fields(values: &[KvPair], fields: &[String]) -> Vec<KvPair> {
+    if fields.is_empty() {
+        return values.to_vec();
+    }
+    values
+        .iter()
+        .filter(|(field, _)| fields.contains(field))
+        .cloned()
+        .collect()
+}
+
+/// Writes the fields of `values` into `record`, overwriting the fields with
+/// the same names and keeping the others.
+fn update_fields(record: &mut Vec<KvPair>, values: &[KvPair]) {
+    for (field, value) in values {
+        match record.iter_mut().find(|(name, _)| name == field) {
+            Some((_, old)) => old.clone_from(value),
+            None => record.push((field.clone(), value.clone())),
+        }
+    }
 }
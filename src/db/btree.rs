@@ -3,10 +3,11 @@ use std::sync::{Arc, RwLock};
 
 use anyhow::Result;
 
-use crate::core::db::{Db, KvPair};
+use super::{select_fields, update_fields};
+use crate::core::db::{Db, KvPair, Record};
 
 pub struct BTreeDb {
-    inner: Arc<RwLock<BTreeMap<String, Vec<KvPair>>>>,
+    inner: Arc<RwLock<BTreeMap<Vec<u8>, Vec<KvPair>>>>,
 }
 
 impl BTreeDb {
@@ -22,31 +23,57 @@ impl Db for BTreeDb {
 
     fn close(&self) {}
 
-    fn read(&self, _: String, key: String, _: Vec<String>) -> Result<Vec<KvPair>> {
+    fn read(&self, _: &str, key: &[u8], fields: &[String]) -> Result<Vec<KvPair>> {
         let db = self.inner.read().unwrap();
-        db.get(&key)
-            .cloned()
-            .ok_or_else(|| anyhow!("key {} does not exist", key))
+        db.get(key)
+            .map(|values| select_fields(values, fields))
+            .ok_or_else(|| anyhow!("key {} does not exist", String::from_utf8_lossy(key)))
     }
 
-    fn scan(&self, _: String, key: String, _: Vec<String>, count: u64) -> Result<Vec<Vec<KvPair>>> {
-        use std::ops::RangeFrom;
+    fn scan(&self, _: &str, key: &[u8], fields: &[String], count: u64) -> Result<Vec<Record>> {
+        use std::ops::Bound;
 
         let db = self.inner.read().unwrap();
         Ok(db
-            .range(RangeFrom { start: key })
+            .range::<[u8], _>((Bound::Included(key), Bound::Unbounded))
             .take(count as usize)
-            .map(|(_, value)| value.clone())
+            .map(|(key, values)| (key.clone(), select_fields(values, fields)))
             .collect())
     }
 
-    fn update(&self, _: String, key: String, values: Vec<KvPair>) -> Result<()> {
+    fn update(&self, _: &str, key: &[u8], values: &[KvPair]) -> Result<()> {
         let mut db = self.inner.write().unwrap();
-        db.insert(key, values);
+        update_fields(db.entry(key.to_vec()).or_default(), values);
         Ok(())
     }
 
-    fn insert(&self, table: String, key: String, values: Vec<KvPair>) -> Result<()> {
-        self.update(table, key, values)
+    fn insert(&self, _: &str, key: &[u8], values: &[KvPair]) -> Result<()> {
+        let mut db = self.inner.write().unwrap();
+        db.insert(key.to_vec(), values.to_vec());
+        Ok(())
+    }
+
+    fn batch_insert(&self, _: &str, records: &[Record]) -> Result<()> {
+        let mut db = self.inner.write().unwrap();
+        db.extend(records.iter().cloned());
+        Ok(())
+    }
+
+    fn multi_get(&self, _: &str, keys: &[Vec<u8>], fields: &[String]) -> Result<Vec<Vec<KvPair>>> {
+        let db = self.inner.read().unwrap();
+        keys.iter()
+            .map(|key| {
+                db.get(key)
+                    .map(|values| select_fields(values, fields))
+                    .ok_or_else(|| anyhow!("key {} does not exist", String::from_utf8_lossy(key)))
+            })
+            .collect()
+    }
+
+    fn delete(&self, _: &str, key: &[u8]) -> Result<()> {
+        let mut db = self.inner.write().unwrap();
+        db.remove(key)
+            .map(|_| ())
+            .ok_or_else(|| anyhow!("key {} does not exist", String::from_utf8_lossy(key)))
     }
 }
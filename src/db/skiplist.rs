@@ -3,11 +3,12 @@ use std::sync::Arc;
 use anyhow::Result;
 use crossbeam_skiplist::map::SkipMap;
 
-use crate::core::db::{Db, KvPair};
+use super::{select_fields, update_fields};
+use crate::core::db::{Db, KvPair, Record};
 
 pub struct SkiplistDb {
     // HashMap<table_name, SkipMap<key, fields>>
-    inner: Arc<SkipMap<String, Vec<KvPair>>>,
+    inner: Arc<SkipMap<Vec<u8>, Vec<KvPair>>>,
 }
 
 impl SkiplistDb {
@@ -23,30 +24,49 @@ impl Db for SkiplistDb {
 
     fn close(&self) {}
 
-    fn read(&self, _: String, key: String, _: Vec<String>) -> Result<Vec<KvPair>> {
+    fn read(&self, _: &str, key: &[u8], fields: &[String]) -> Result<Vec<KvPair>> {
         self.inner
-            .get(&key)
-            .ok_or_else(|| anyhow!("key {} does not exist", key))
-            .map(|e| e.value().clone())
+            .get(key)
+            .ok_or_else(|| anyhow!("key {} does not exist", String::from_utf8_lossy(key)))
+            .map(|e| select_fields(e.value(), fields))
     }
 
-    fn scan(&self, _: String, key: String, _: Vec<String>, count: u64) -> Result<Vec<Vec<KvPair>>> {
-        use std::ops::RangeFrom;
+    fn scan(&self, _: &str, key: &[u8], fields: &[String], count: u64) -> Result<Vec<Record>> {
+        use std::ops::Bound;
 
         Ok(self
             .inner
-            .range(RangeFrom { start: key })
+            .range::<[u8], _>((Bound::Included(key), Bound::Unbounded))
             .take(count as usize)
-            .map(|e| e.value().clone())
+            .map(|e| (e.key().clone(), select_fields(e.value(), fields)))
             .collect())
     }
 
-    fn update(&self, _: String, key: String, values: Vec<KvPair>) -> Result<()> {
-        self.inner.insert(key, values);
+    fn update(&self, _: &str, key: &[u8], values: &[KvPair]) -> Result<()> {
+        // Not atomic: of two concurrent updates of different fields of a
+        // record, the one inserted last may undo the other.
+        let mut record = self
+            .inner
+            .get(key)
+            .map(|e| e.value().clone())
+            .unwrap_or_default();
+        update_fields(&mut record, values);
+        self.inner.insert(key.to_vec(), record);
         Ok(())
     }
 
-    fn insert(&self, table: String, key: String, values: Vec<KvPair>) -> Result<()> {
-        self.update(table, key, values)
+    fn insert(&self, _: &str, key: &[u8], values: &[KvPair]) -> Result<()> {
+        self.inner.insert(key.to_vec(), values.to_vec());
+        Ok(())
+    }
+
+    // `batch_insert` is left to the default loop over `insert`: a `SkipMap`
+    // has no bulk insert, and its inserts never block each other.
+
+    fn delete(&self, _: &str, key: &[u8]) -> Result<()> {
+        self.inner
+            .remove(key)
+            .map(|_| ())
+            .ok_or_else(|| anyhow!("key {} does not exist", String::from_utf8_lossy(key)))
     }
 }
@@ -1,10 +1,9 @@
-#![feature(const_fn)]
-
 #[macro_use]
 extern crate anyhow;
 
 #[macro_use]
 extern crate lazy_static;
 
+pub mod cli;
 pub mod core;
 pub mod db;
@@ -0,0 +1,50 @@
+use anyhow::Result;
+
+use ycsb::core::benchmark::{Benchmark, BenchmarkResult, Phase};
+use ycsb::core::properties::Properties;
+use ycsb::core::workload::create_workload;
+use ycsb::db::create_db;
+
+const PROPERTIES: &str = "
+recordcount=1000
+operationcount=2000
+readproportion=0.5
+updateproportion=0.5
+";
+
+fn count(result: &BenchmarkResult, name: &str) -> u64 {
+    result
+        .operations
+        .iter()
+        .find(|(op, _)| op == name)
+        .map_or(0, |(_, info)| info.count)
+}
+
+#[test]
+fn test_load_and_run() -> Result<()> {
+    let props = Properties::load(PROPERTIES.as_bytes())?;
+    let db = create_db("btree", &props)?;
+    let workload = create_workload(&props)?;
+    let benchmark = Benchmark::new(props, db).threads(3).workload(workload);
+
+    let load = benchmark.clone().phase(Phase::Load).run()?;
+    assert_eq!(load.count, 1000);
+    assert_eq!(count(&load, "INSERT"), 1000);
+
+    let run = benchmark.phase(Phase::Run).run()?;
+    assert_eq!(run.count, 2000);
+    assert_eq!(count(&run, "READ") + count(&run, "UPDATE"), 2000);
+    for (name, info) in &run.operations {
+        assert_eq!(info.errors, 0, "{}", name);
+    }
+    assert!(run.anomaly.is_none());
+    Ok(())
+}
+
+#[test]
+fn test_zero_threads() -> Result<()> {
+    let props = Properties::load(PROPERTIES.as_bytes())?;
+    let db = create_db("btree", &props)?;
+    assert!(Benchmark::new(props, db).threads(0).run().is_err());
+    Ok(())
+}